@@ -0,0 +1,372 @@
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use std::fmt;
+use std::io::{copy, BufRead, Read, Write};
+
+#[cfg(feature = "brotli")]
+mod brotli;
+#[cfg(feature = "bzip2")]
+mod bzip2;
+mod gzip;
+#[cfg(feature = "lz4")]
+mod lz4;
+#[cfg(feature = "lzw")]
+mod lzw;
+#[cfg(feature = "xz")]
+mod xz;
+#[cfg(feature = "zstd")]
+mod zstd;
+
+pub use gzip::{gzip_header, gzip_mtime, is_gzip, os_name, GzipHeader};
+
+/// A compression format that input files can be stored in.
+pub trait Decompressor: Sync {
+    /// Short name shown in the run summary.
+    fn name(&self) -> &'static str;
+
+    /// Bytes every stream of this format starts with, empty when the format
+    /// has none.
+    fn magic(&self) -> &'static [u8];
+
+    /// File extensions, with the leading dot, used when no magic bytes
+    /// match.
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// Wraps `reader` into a reader over the decompressed content.
+    fn reader<'a>(&self, reader: Box<dyn BufRead + 'a>) -> Result<Box<dyn Read + 'a>>;
+
+    /// Decompresses `reader` into `writer` and returns the number of members
+    /// when the format keeps track of them.
+    fn decompress(
+        &self,
+        reader: Box<dyn BufRead + '_>,
+        writer: &mut dyn Write,
+    ) -> Result<Option<usize>> {
+        copy(&mut self.reader(reader)?, writer)?;
+        Ok(None)
+    }
+
+    /// Like [`Decompressor::decompress`], but keeps what decoded before
+    /// damaged data instead of failing and, with `resync`, resumes decoding
+    /// after it. Formats without recovery fail as `decompress` does.
+    fn recover(
+        &self,
+        reader: Box<dyn BufRead + '_>,
+        writer: &mut dyn Write,
+        resync: bool,
+    ) -> Result<(Option<usize>, Vec<Damage>)> {
+        let _ = resync;
+        Ok((self.decompress(reader, writer)?, Vec::new()))
+    }
+}
+
+/// How damaged compressed input is handled.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Recovery {
+    /// Keep what decoded before the damage and drop the rest of the file.
+    Salvage,
+    /// Also look past the damage for the next point decoding can resume
+    /// from.
+    Resync,
+}
+
+/// Damaged stretch of compressed input met while recovering it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Damage {
+    /// Offset in the compressed file where decoding failed.
+    pub offset: u64,
+    /// Offset in the decoded content of the file at that point.
+    pub decoded: u64,
+    /// Compressed bytes dropped. Decoding resumed after them unless they
+    /// run to the end of the file.
+    pub skipped: u64,
+    pub error: String,
+}
+
+impl fmt::Display for Damage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "lost {} compressed bytes at offset {} (decoded offset {}): {}",
+            self.skipped, self.offset, self.decoded, self.error
+        )
+    }
+}
+
+/// Decompressors compiled into this build, in detection order.
+static CODECS: &[&dyn Decompressor] = &[
+    &gzip::Gzip,
+    #[cfg(feature = "zstd")]
+    &zstd::Zstd,
+    #[cfg(feature = "xz")]
+    &xz::Xz,
+    #[cfg(feature = "bzip2")]
+    &bzip2::Bzip2,
+    #[cfg(feature = "lz4")]
+    &lz4::Lz4,
+    #[cfg(feature = "lzw")]
+    &lzw::Lzw,
+    #[cfg(feature = "brotli")]
+    &brotli::Brotli,
+];
+
+/// Magic bytes of every supported format with the cargo feature enabling
+/// it, so that a slim build rejects such files instead of copying them as
+/// text.
+const KNOWN_MAGIC: [(&[u8], &str); 5] = [
+    (&[0x28, 0xb5, 0x2f, 0xfd], "zstd"),
+    (&[0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00], "xz"),
+    (b"BZh", "bzip2"),
+    (&[0x04, 0x22, 0x4d, 0x18], "lz4"),
+    (&[0x1f, 0x9d], "lzw"),
+];
+
+/// Picks the decompressor for a stream starting with `header`, falling back
+/// on the extension of `path` for formats without magic bytes or with a
+/// damaged header. `None` means plain text.
+fn detect(header: &[u8], path: &str) -> Result<Option<&'static dyn Decompressor>> {
+    let by_magic = CODECS
+        .iter()
+        .find(|codec| !codec.magic().is_empty() && header.starts_with(codec.magic()));
+    if let Some(codec) = by_magic {
+        return Ok(Some(*codec));
+    }
+
+    if let Some((_, feature)) = KNOWN_MAGIC
+        .iter()
+        .find(|(magic, _)| header.starts_with(magic))
+    {
+        bail!(
+            "{} looks like {} data, but this build has no `{}` feature",
+            path,
+            feature,
+            feature
+        );
+    }
+
+    Ok(CODECS
+        .iter()
+        .find(|codec| {
+            codec
+                .extensions()
+                .iter()
+                .any(|extension| path.ends_with(extension))
+        })
+        .copied())
+}
+
+/// How an input file was stored on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Uncompressed text, e.g. the live log or a `delaycompress` rotation.
+    Plain,
+    Compressed {
+        codec: &'static str,
+        /// Number of members, for formats that report it.
+        members: Option<usize>,
+    },
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Encoding::Plain => write!(f, "plain text"),
+            Encoding::Compressed {
+                codec,
+                members: Some(members),
+            } => write!(f, "{} {} member(s)", members, codec),
+            Encoding::Compressed {
+                codec,
+                members: None,
+            } => write!(f, "{}", codec),
+        }
+    }
+}
+
+/// Writes the content of `reader` into `writer`, decompressing it with the
+/// codec detected from its magic bytes or the extension of `path`, and
+/// copying it verbatim otherwise.
+pub fn decompress_into<'a, R: BufRead + 'a, W: Write>(
+    mut reader: R,
+    path: &str,
+    writer: &mut W,
+) -> Result<Encoding> {
+    match detect(reader.fill_buf()?, path)? {
+        Some(codec) => {
+            let members = codec.decompress(Box::new(reader), writer)?;
+            Ok(Encoding::Compressed {
+                codec: codec.name(),
+                members,
+            })
+        }
+        None => {
+            copy(&mut reader, writer)?;
+            Ok(Encoding::Plain)
+        }
+    }
+}
+
+/// Decompresses the gzip members of `reader` into `writer`, without
+/// detecting the format first.
+pub fn decompress_gzip_into<'a, R: BufRead + 'a, W: Write>(
+    reader: R,
+    writer: &mut W,
+) -> Result<Encoding> {
+    let codec = gzip::Gzip;
+    let members = codec.decompress(Box::new(reader), writer)?;
+    Ok(Encoding::Compressed {
+        codec: codec.name(),
+        members,
+    })
+}
+
+/// Like [`decompress_into`], but damaged data is handled as `recovery` says
+/// instead of failing, and the damage found is returned.
+pub fn recover_into<'a, R: BufRead + 'a, W: Write>(
+    mut reader: R,
+    path: &str,
+    writer: &mut W,
+    recovery: Recovery,
+) -> Result<(Encoding, Vec<Damage>)> {
+    match detect(reader.fill_buf()?, path)? {
+        Some(codec) => {
+            let (members, damage) =
+                codec.recover(Box::new(reader), writer, recovery == Recovery::Resync)?;
+            let encoding = Encoding::Compressed {
+                codec: codec.name(),
+                members,
+            };
+            Ok((encoding, damage))
+        }
+        None => {
+            copy(&mut reader, writer)?;
+            Ok((Encoding::Plain, Vec::new()))
+        }
+    }
+}
+
+/// Returns a reader over the decompressed content of `reader`, for callers
+/// that only need to peek into a file.
+pub fn decoded_reader<'a, R: BufRead + 'a>(
+    mut reader: R,
+    path: &str,
+) -> Result<Box<dyn Read + 'a>> {
+    match detect(reader.fill_buf()?, path)? {
+        Some(codec) => codec.reader(Box::new(reader)),
+        None => Ok(Box::new(reader)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use super::{decompress_into, gzip_mtime, Encoding};
+    use anyhow::Result;
+
+    /// Decompresses a fixture named `path` and returns what came out.
+    pub fn decompress_fixture(buffer: &[u8], path: &str) -> Result<(Encoding, String)> {
+        let reader = Cursor::new(buffer);
+        let mut writer_buf: Vec<u8> = Vec::new();
+        let mut writer = Cursor::new(&mut writer_buf);
+
+        let encoding = decompress_into(reader, path, &mut writer)?;
+
+        writer.flush()?;
+        Ok((encoding, String::from_utf8(writer_buf)?))
+    }
+
+    #[test]
+    fn decompress_test() -> Result<()> {
+        let buffer = [
+            0x1f, 0x8b, 0x8, 0x8, 0x60, 0x6d, 0xd8, 0x62, 0x0, 0x3, 0x69, 0x6e, 0x2e, 0x74, 0x78,
+            0x74, 0x0, 0xf3, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0x8, 0xcf, 0x2f, 0xca, 0x49, 0xe1, 0x2,
+            0x0, 0xe3, 0xe5, 0x95, 0xb0, 0xc, 0x0, 0x0, 0x0,
+        ];
+
+        let reader = Cursor::new(&buffer);
+        let mut writer_buf: Vec<u8> = Vec::new();
+        let mut writer = Cursor::new(&mut writer_buf);
+
+        decompress_into(reader, "in.txt.gz", &mut writer)?;
+
+        writer.flush()?;
+        let got = String::from_utf8(writer_buf)?;
+        let expected = String::from("Hello World\n");
+
+        assert_eq!(got, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn gzip_mtime_test() -> Result<()> {
+        let buffer = [
+            0x1f, 0x8b, 0x8, 0x8, 0x60, 0x6d, 0xd8, 0x62, 0x0, 0x3, 0x69, 0x6e, 0x2e, 0x74, 0x78,
+        ];
+        assert_eq!(gzip_mtime(Cursor::new(&buffer))?, Some(0x62d86d60));
+        assert_eq!(gzip_mtime(Cursor::new(b"plain text"))?, None);
+        assert_eq!(gzip_mtime(Cursor::new(b""))?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn decompress_multi_member_test() -> Result<()> {
+        // `cat a.txt.gz b.txt.gz`
+        let buffer = [
+            0x1f, 0x8b, 0x8, 0x8, 0x60, 0x6d, 0xd8, 0x62, 0x2, 0xff, 0x61, 0x2e, 0x74, 0x78, 0x74,
+            0x0, 0xf3, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0x8, 0xcf, 0x2f, 0xca, 0x49, 0xe1, 0x2, 0x0,
+            0xe3, 0xe5, 0x95, 0xb0, 0xc, 0x0, 0x0, 0x0, 0x1f, 0x8b, 0x8, 0x8, 0x60, 0x6d, 0xd8,
+            0x62, 0x2, 0xff, 0x62, 0x2e, 0x74, 0x78, 0x74, 0x0, 0xb, 0x4e, 0x4d, 0xce, 0xcf, 0x4b,
+            0x51, 0xc8, 0x4d, 0xcd, 0x4d, 0x4a, 0x2d, 0xe2, 0x2, 0x0, 0xee, 0x60, 0x2f, 0xc3, 0xe,
+            0x0, 0x0, 0x0,
+        ];
+
+        let reader = Cursor::new(&buffer);
+        let mut writer_buf: Vec<u8> = Vec::new();
+        let mut writer = Cursor::new(&mut writer_buf);
+
+        let encoding = decompress_into(reader, "in.txt.gz", &mut writer)?;
+
+        writer.flush()?;
+        let got = String::from_utf8(writer_buf)?;
+        let expected = String::from("Hello World\nSecond member\n");
+
+        assert_eq!(
+            encoding,
+            Encoding::Compressed {
+                codec: "gzip",
+                members: Some(2)
+            }
+        );
+        assert_eq!(got, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn decompress_plain_test() -> Result<()> {
+        let buffer = b"Hello World\n";
+
+        let reader = Cursor::new(&buffer);
+        let mut writer_buf: Vec<u8> = Vec::new();
+        let mut writer = Cursor::new(&mut writer_buf);
+
+        let encoding = decompress_into(reader, "in.txt", &mut writer)?;
+
+        writer.flush()?;
+        let got = String::from_utf8(writer_buf)?;
+
+        assert_eq!(encoding, Encoding::Plain);
+        assert_eq!(got, "Hello World\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn decompress_corrupt_by_extension_test() {
+        // Not gzip despite the name: report it instead of copying garbage.
+        assert!(decompress_fixture(b"Hello World\n", "in.txt.gz").is_err());
+    }
+}
@@ -0,0 +1,59 @@
+use anyhow::Result;
+use std::io::{BufRead, Read};
+
+use super::Decompressor;
+
+const BUFFER_SIZE: usize = 4096;
+
+/// Brotli streams have no magic bytes, so these files are only recognized
+/// by their `.br` extension.
+pub struct Brotli;
+
+impl Decompressor for Brotli {
+    fn name(&self) -> &'static str {
+        "brotli"
+    }
+
+    fn magic(&self) -> &'static [u8] {
+        &[]
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &[".br"]
+    }
+
+    fn reader<'a>(&self, reader: Box<dyn BufRead + 'a>) -> Result<Box<dyn Read + 'a>> {
+        Ok(Box::new(brotli_decompressor::Decompressor::new(
+            reader,
+            BUFFER_SIZE,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::decompress_fixture;
+    use super::super::Encoding;
+    use anyhow::Result;
+
+    #[test]
+    fn decompress_brotli_test() -> Result<()> {
+        let buffer = [
+            0x8b, 0x5, 0x80, 0x48, 0x65, 0x6c, 0x6c, 0x6f, 0x20, 0x57, 0x6f, 0x72, 0x6c, 0x64, 0xa,
+            0x3,
+        ];
+
+        let (encoding, got) = decompress_fixture(&buffer, "in.txt.br")?;
+
+        assert_eq!(
+            encoding,
+            Encoding::Compressed {
+                codec: "brotli",
+                members: None
+            }
+        );
+        assert_eq!(got, "Hello World\n");
+
+        Ok(())
+    }
+}
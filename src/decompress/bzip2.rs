@@ -0,0 +1,54 @@
+use anyhow::Result;
+use std::io::{BufRead, Read};
+
+use super::Decompressor;
+
+pub struct Bzip2;
+
+impl Decompressor for Bzip2 {
+    fn name(&self) -> &'static str {
+        "bzip2"
+    }
+
+    fn magic(&self) -> &'static [u8] {
+        b"BZh"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &[".bz2"]
+    }
+
+    fn reader<'a>(&self, reader: Box<dyn BufRead + 'a>) -> Result<Box<dyn Read + 'a>> {
+        Ok(Box::new(::bzip2::bufread::MultiBzDecoder::new(reader)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::decompress_fixture;
+    use super::super::Encoding;
+    use anyhow::Result;
+
+    #[test]
+    fn decompress_bzip2_test() -> Result<()> {
+        let buffer = [
+            0x42, 0x5a, 0x68, 0x39, 0x31, 0x41, 0x59, 0x26, 0x53, 0x59, 0xd8, 0x72, 0x1, 0x2f, 0x0,
+            0x0, 0x1, 0x57, 0x80, 0x0, 0x10, 0x40, 0x0, 0x0, 0x40, 0x0, 0x80, 0x6, 0x4, 0x90, 0x0,
+            0x20, 0x0, 0x22, 0x6, 0x86, 0xd4, 0x20, 0xc9, 0x88, 0xc7, 0x69, 0xe8, 0x28, 0x1f, 0x8b,
+            0xb9, 0x22, 0x9c, 0x28, 0x48, 0x6c, 0x39, 0x0, 0x97, 0x80,
+        ];
+
+        let (encoding, got) = decompress_fixture(&buffer, "in.txt.bz2")?;
+
+        assert_eq!(
+            encoding,
+            Encoding::Compressed {
+                codec: "bzip2",
+                members: None
+            }
+        );
+        assert_eq!(got, "Hello World\n");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,338 @@
+use anyhow::{Context, Result};
+use flate2::bufread::{DeflateDecoder, GzDecoder, MultiGzDecoder};
+use std::io::{self, copy, sink, BufRead, ErrorKind, Read, Write};
+
+use super::{Damage, Decompressor};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Empty stored block that zlib writes on a full flush. Deflate data right
+/// after it does not refer back to anything before it.
+const FLUSH_MARKER: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// Decoded bytes a candidate resync point has to produce without error to be
+/// taken.
+const PROBE_BYTES: u64 = 64 * 1024;
+
+pub struct Gzip;
+
+impl Decompressor for Gzip {
+    fn name(&self) -> &'static str {
+        "gzip"
+    }
+
+    fn magic(&self) -> &'static [u8] {
+        &GZIP_MAGIC
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &[".gz"]
+    }
+
+    fn reader<'a>(&self, reader: Box<dyn BufRead + 'a>) -> Result<Box<dyn Read + 'a>> {
+        Ok(Box::new(MultiGzDecoder::new(reader)))
+    }
+
+    /// Decompresses every gzip member of `reader`. Archives produced by
+    /// `cat a.gz b.gz`, pigz or appending log shippers consist of several
+    /// members back to back.
+    fn decompress(
+        &self,
+        mut reader: Box<dyn BufRead + '_>,
+        writer: &mut dyn Write,
+    ) -> Result<Option<usize>> {
+        let mut members = 0;
+
+        loop {
+            let mut decoder = GzDecoder::new(reader);
+            copy(&mut decoder, writer)
+                .with_context(|| format!("Failed to decode gzip member #{}", members + 1))?;
+            members += 1;
+
+            reader = decoder.into_inner();
+            if reader.fill_buf()?.is_empty() {
+                break;
+            }
+        }
+
+        Ok(Some(members))
+    }
+
+    /// Reads the whole file into memory, since resyncing needs to try
+    /// decoding from candidate offsets and back off.
+    fn recover(
+        &self,
+        mut reader: Box<dyn BufRead + '_>,
+        writer: &mut dyn Write,
+        resync: bool,
+    ) -> Result<(Option<usize>, Vec<Damage>)> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        let mut members = 0;
+        let mut decoded = 0;
+        let mut damage = Vec::new();
+        let mut start = Start::Member;
+        let mut position = 0;
+
+        while position < data.len() {
+            let segment = decode_segment(&data[position..], start, writer, u64::MAX)?;
+            decoded += segment.decoded;
+            if start == Start::Member && (segment.error.is_none() || segment.decoded > 0) {
+                members += 1;
+            }
+
+            let Some(error) = segment.error else {
+                position += segment.consumed;
+                start = Start::Member;
+                continue;
+            };
+
+            // Nothing decoded means the damage starts with the segment, the
+            // decoder having read into it to find out.
+            let failed_at = if segment.decoded == 0 {
+                position
+            } else {
+                position + segment.consumed
+            };
+
+            // A candidate at the start of the failed segment is known bad.
+            let resumed = if resync {
+                find_resync(&data, failed_at.max(position + 1))
+            } else {
+                None
+            };
+            let resume_at = resumed.map_or(data.len(), |(offset, _)| offset);
+            damage.push(Damage {
+                offset: failed_at as u64,
+                decoded,
+                skipped: (resume_at - failed_at) as u64,
+                error: error.to_string(),
+            });
+
+            position = resume_at;
+            start = resumed.map_or(Start::Member, |(_, start)| start);
+        }
+
+        Ok((Some(members), damage))
+    }
+}
+
+/// What recovery resumes decoding with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Start {
+    /// A gzip member header.
+    Member,
+    /// Raw deflate data after a full flush, followed by the trailer of the
+    /// member it belongs to.
+    Deflate,
+}
+
+struct Segment {
+    /// Compressed bytes read.
+    consumed: usize,
+    decoded: u64,
+    /// Why decoding stopped before the end of the segment.
+    error: Option<io::Error>,
+}
+
+/// Decodes the member or deflate stream at the start of `data` into
+/// `writer`, stopping without error once `limit` bytes are decoded. Only
+/// failures to write are returned as errors.
+fn decode_segment(
+    data: &[u8],
+    start: Start,
+    writer: &mut dyn Write,
+    limit: u64,
+) -> io::Result<Segment> {
+    fn pump<R: Read>(
+        decoder: &mut R,
+        writer: &mut dyn Write,
+        limit: u64,
+    ) -> io::Result<(u64, Option<io::Error>)> {
+        let mut buffer = [0u8; 32 * 1024];
+        let mut decoded = 0;
+        while decoded < limit {
+            let read = match decoder.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+                Err(error) => return Ok((decoded, Some(error))),
+            };
+            writer.write_all(&buffer[..read])?;
+            decoded += read as u64;
+        }
+        Ok((decoded, None))
+    }
+
+    let (consumed, decoded, error) = match start {
+        Start::Member => {
+            let mut decoder = GzDecoder::new(data);
+            let (decoded, error) = pump(&mut decoder, writer, limit)?;
+            (data.len() - decoder.get_ref().len(), decoded, error)
+        }
+        Start::Deflate => {
+            let mut decoder = DeflateDecoder::new(data);
+            let (decoded, error) = pump(&mut decoder, writer, limit)?;
+            let mut consumed = data.len() - decoder.get_ref().len();
+            if error.is_none() && decoded < limit {
+                // CRC32 and ISIZE of the member, which cannot be checked
+                // without its beginning.
+                consumed = (consumed + 8).min(data.len());
+            }
+            (consumed, decoded, error)
+        }
+    };
+
+    Ok(Segment {
+        consumed,
+        decoded,
+        error,
+    })
+}
+
+/// Finds the first offset from `from` on where decoding can resume: a gzip
+/// member header or the end of a full flush. Candidates have to decode
+/// cleanly for a while to be taken.
+fn find_resync(data: &[u8], from: usize) -> Option<(usize, Start)> {
+    (from..data.len()).find_map(|offset| {
+        let rest = &data[offset..];
+        let candidate = if rest.starts_with(&[GZIP_MAGIC[0], GZIP_MAGIC[1], 0x08])
+            && rest.get(3).is_some_and(|flags| flags & 0xe0 == 0)
+        {
+            (offset, Start::Member)
+        } else if rest.starts_with(&FLUSH_MARKER) {
+            (offset + FLUSH_MARKER.len(), Start::Deflate)
+        } else {
+            return None;
+        };
+
+        let probe = decode_segment(&data[candidate.0..], candidate.1, &mut sink(), PROBE_BYTES);
+        match probe {
+            Ok(segment) if segment.error.is_none() && segment.consumed > 0 => Some(candidate),
+            _ => None,
+        }
+    })
+}
+
+/// Returns true when `header` starts like a gzip member.
+pub fn is_gzip(header: &[u8]) -> bool {
+    header.starts_with(&GZIP_MAGIC)
+}
+
+/// Fields of the first gzip member header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GzipHeader {
+    /// Original file name (FNAME), when stored.
+    pub name: Option<String>,
+    /// MTIME in seconds since the epoch, `None` when unset.
+    pub mtime: Option<u32>,
+    /// OS field, see [`os_name`].
+    pub os: u8,
+}
+
+/// Parses the header of the first gzip member. Returns `None` for input that
+/// is not gzip.
+pub fn gzip_header<R: BufRead>(mut reader: R) -> Result<Option<GzipHeader>> {
+    if !is_gzip(reader.fill_buf()?) {
+        return Ok(None);
+    }
+
+    let decoder = GzDecoder::new(reader);
+    let header = decoder.header().context("Damaged gzip header")?;
+    Ok(Some(GzipHeader {
+        name: header
+            .filename()
+            .map(|name| String::from_utf8_lossy(name).into_owned()),
+        mtime: (header.mtime() != 0).then_some(header.mtime()),
+        os: header.operating_system(),
+    }))
+}
+
+/// Name of the system a gzip member was made on, from RFC 1952.
+pub fn os_name(os: u8) -> &'static str {
+    match os {
+        0 => "FAT",
+        1 => "Amiga",
+        2 => "VMS",
+        3 => "Unix",
+        4 => "VM/CMS",
+        5 => "Atari TOS",
+        6 => "HPFS",
+        7 => "Macintosh",
+        8 => "Z-System",
+        9 => "CP/M",
+        10 => "TOPS-20",
+        11 => "NTFS",
+        12 => "QDOS",
+        13 => "Acorn RISCOS",
+        _ => "unknown",
+    }
+}
+
+/// Reads the MTIME field of the first gzip member header. Returns `None` for
+/// input that is not gzip or when the field is unset, as `gzip -n` does.
+pub fn gzip_mtime<R: BufRead>(mut reader: R) -> Result<Option<u32>> {
+    let mut header = [0u8; 10];
+    match reader.read_exact(&mut header) {
+        Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+    if !is_gzip(&header) {
+        return Ok(None);
+    }
+
+    let mtime = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    Ok((mtime != 0).then_some(mtime))
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::{BufReader, Write};
+
+    use super::Gzip;
+    use crate::decompress::Decompressor;
+    use anyhow::Result;
+
+    fn member(text: &str) -> Result<Vec<u8>> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(text.as_bytes())?;
+        Ok(encoder.finish()?)
+    }
+
+    fn recover(data: &[u8], resync: bool) -> Result<(String, Vec<u64>)> {
+        let mut decoded = Vec::new();
+        let (_, damage) = Gzip.recover(Box::new(BufReader::new(data)), &mut decoded, resync)?;
+        let skipped = damage.iter().map(|damage| damage.skipped).collect();
+        Ok((String::from_utf8(decoded)?, skipped))
+    }
+
+    #[test]
+    fn recover_test() -> Result<()> {
+        let first = member("first member\n")?;
+        let second = member("second member\n")?;
+
+        // Second member cut before its trailer.
+        let mut truncated = first.clone();
+        truncated.extend_from_slice(&second[..second.len() - 4]);
+        let (decoded, skipped) = recover(&truncated, false)?;
+        assert_eq!(decoded, "first member\nsecond member\n");
+        assert_eq!(skipped, vec![0]);
+
+        // Garbage between members is skipped up to the next header.
+        let mut damaged = first.clone();
+        damaged.extend_from_slice(b"garbage");
+        damaged.extend_from_slice(&second);
+        let (decoded, skipped) = recover(&damaged, false)?;
+        assert_eq!(decoded, "first member\n");
+        assert_eq!(skipped, vec![7 + second.len() as u64]);
+
+        let (decoded, skipped) = recover(&damaged, true)?;
+        assert_eq!(decoded, "first member\nsecond member\n");
+        assert_eq!(skipped, vec![7]);
+
+        Ok(())
+    }
+}
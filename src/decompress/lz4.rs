@@ -0,0 +1,53 @@
+use anyhow::Result;
+use std::io::{BufRead, Read};
+
+use super::Decompressor;
+
+pub struct Lz4;
+
+impl Decompressor for Lz4 {
+    fn name(&self) -> &'static str {
+        "lz4"
+    }
+
+    fn magic(&self) -> &'static [u8] {
+        &[0x04, 0x22, 0x4d, 0x18]
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &[".lz4"]
+    }
+
+    fn reader<'a>(&self, reader: Box<dyn BufRead + 'a>) -> Result<Box<dyn Read + 'a>> {
+        Ok(Box::new(lz4_flex::frame::FrameDecoder::new(reader)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::decompress_fixture;
+    use super::super::Encoding;
+    use anyhow::Result;
+
+    #[test]
+    fn decompress_lz4_test() -> Result<()> {
+        let buffer = [
+            0x4, 0x22, 0x4d, 0x18, 0x64, 0x40, 0xa7, 0xc, 0x0, 0x0, 0x80, 0x48, 0x65, 0x6c, 0x6c,
+            0x6f, 0x20, 0x57, 0x6f, 0x72, 0x6c, 0x64, 0xa, 0x0, 0x0, 0x0, 0x0, 0xbd, 0x5, 0x8e,
+            0xd6,
+        ];
+
+        let (encoding, got) = decompress_fixture(&buffer, "in.txt.lz4")?;
+
+        assert_eq!(
+            encoding,
+            Encoding::Compressed {
+                codec: "lz4",
+                members: None
+            }
+        );
+        assert_eq!(got, "Hello World\n");
+
+        Ok(())
+    }
+}
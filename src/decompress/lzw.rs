@@ -0,0 +1,278 @@
+use anyhow::{bail, Result};
+use std::io::{self, BufRead, Read};
+
+use super::Decompressor;
+
+const LZW_MAGIC: [u8; 2] = [0x1f, 0x9d];
+const INIT_BITS: u32 = 9;
+const MAX_BITS: u32 = 16;
+const CLEAR: u32 = 256;
+
+/// `.Z` files written by the Unix `compress` utility.
+pub struct Lzw;
+
+impl Decompressor for Lzw {
+    fn name(&self) -> &'static str {
+        "lzw"
+    }
+
+    fn magic(&self) -> &'static [u8] {
+        &LZW_MAGIC
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &[".Z"]
+    }
+
+    fn reader<'a>(&self, reader: Box<dyn BufRead + 'a>) -> Result<Box<dyn Read + 'a>> {
+        Ok(Box::new(LzwDecoder::new(reader)?))
+    }
+}
+
+/// Streaming decoder following `unlzw` from gzip, including the quirk that
+/// `compress` pads the codes to a whole group of `n_bits` bytes whenever the
+/// code width changes or the table is cleared.
+struct LzwDecoder<R> {
+    inner: R,
+    block_mode: bool,
+    max_bits: u32,
+    n_bits: u32,
+    max_code: u32,
+    free_entry: u32,
+    prefix: Vec<u16>,
+    suffix: Vec<u8>,
+    old_code: Option<u32>,
+    last_char: u8,
+    bit_buf: u64,
+    bit_count: u32,
+    /// Bits consumed since the code width last changed.
+    segment_bits: u64,
+    /// Decoded bytes not yet returned, in reverse order.
+    pending: Vec<u8>,
+    done: bool,
+}
+
+impl<R: BufRead> LzwDecoder<R> {
+    fn new(mut inner: R) -> Result<Self> {
+        let mut header = [0u8; 3];
+        inner.read_exact(&mut header)?;
+        if header[..2] != LZW_MAGIC {
+            bail!("Not a compress (.Z) stream");
+        }
+
+        let max_bits = u32::from(header[2] & 0x1f);
+        if !(INIT_BITS..=MAX_BITS).contains(&max_bits) {
+            bail!("Unsupported compress code width ({} bits)", max_bits);
+        }
+        let block_mode = header[2] & 0x80 != 0;
+
+        Ok(Self {
+            inner,
+            block_mode,
+            max_bits,
+            n_bits: INIT_BITS,
+            max_code: (1 << INIT_BITS) - 1,
+            free_entry: if block_mode { CLEAR + 1 } else { CLEAR },
+            prefix: vec![0; 1 << max_bits],
+            suffix: (0..1usize << max_bits).map(|code| code as u8).collect(),
+            old_code: None,
+            last_char: 0,
+            bit_buf: 0,
+            bit_count: 0,
+            segment_bits: 0,
+            pending: Vec::new(),
+            done: false,
+        })
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let byte = self.inner.fill_buf()?.first().copied();
+        if byte.is_some() {
+            self.inner.consume(1);
+        }
+        Ok(byte)
+    }
+
+    /// Skips the padding up to the end of the current group of codes.
+    fn skip_to_group_end(&mut self) -> io::Result<()> {
+        let group = u64::from(self.n_bits) * 8;
+        let mut padding = (group - self.segment_bits % group) % group;
+
+        while padding > 0 {
+            if self.bit_count == 0 {
+                match self.read_byte()? {
+                    Some(byte) => {
+                        self.bit_buf = u64::from(byte);
+                        self.bit_count = 8;
+                    }
+                    None => break,
+                }
+            }
+            let skip = padding.min(u64::from(self.bit_count)) as u32;
+            self.bit_buf >>= skip;
+            self.bit_count -= skip;
+            padding -= u64::from(skip);
+        }
+
+        self.segment_bits = 0;
+        Ok(())
+    }
+
+    fn read_code(&mut self) -> io::Result<Option<u32>> {
+        while self.bit_count < self.n_bits {
+            match self.read_byte()? {
+                Some(byte) => {
+                    self.bit_buf |= u64::from(byte) << self.bit_count;
+                    self.bit_count += 8;
+                }
+                // Fewer bits than a code are the padding of the last byte.
+                None => return Ok(None),
+            }
+        }
+
+        let code = (self.bit_buf & ((1 << self.n_bits) - 1)) as u32;
+        self.bit_buf >>= self.n_bits;
+        self.bit_count -= self.n_bits;
+        self.segment_bits += u64::from(self.n_bits);
+        Ok(Some(code))
+    }
+
+    /// Decodes the next code into `pending`.
+    fn decode_next(&mut self) -> io::Result<()> {
+        let max_max_code = 1u32 << self.max_bits;
+
+        if self.free_entry > self.max_code {
+            self.skip_to_group_end()?;
+            self.n_bits += 1;
+            self.max_code = if self.n_bits == self.max_bits {
+                max_max_code
+            } else {
+                (1 << self.n_bits) - 1
+            };
+        }
+
+        let code = match self.read_code()? {
+            Some(code) => code,
+            None => {
+                self.done = true;
+                return Ok(());
+            }
+        };
+
+        let old_code = match self.old_code {
+            Some(old_code) => old_code,
+            None => {
+                if code >= CLEAR {
+                    return Err(corrupt());
+                }
+                self.old_code = Some(code);
+                self.last_char = code as u8;
+                self.pending.push(self.last_char);
+                return Ok(());
+            }
+        };
+
+        if code == CLEAR && self.block_mode {
+            // The entry after a clear is thrown away, exactly as in unlzw.
+            self.free_entry = CLEAR;
+            self.skip_to_group_end()?;
+            self.n_bits = INIT_BITS;
+            self.max_code = (1 << INIT_BITS) - 1;
+            return Ok(());
+        }
+
+        let mut current = code;
+        if current >= self.free_entry {
+            // The KwKwK case: the code being defined right now.
+            if current > self.free_entry {
+                return Err(corrupt());
+            }
+            self.pending.push(self.last_char);
+            current = old_code;
+        }
+        while current >= CLEAR {
+            self.pending.push(self.suffix[current as usize]);
+            current = u32::from(self.prefix[current as usize]);
+        }
+        self.last_char = current as u8;
+        self.pending.push(self.last_char);
+
+        if self.free_entry < max_max_code {
+            self.prefix[self.free_entry as usize] = old_code as u16;
+            self.suffix[self.free_entry as usize] = self.last_char;
+            self.free_entry += 1;
+        }
+        self.old_code = Some(code);
+
+        Ok(())
+    }
+}
+
+fn corrupt() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "corrupt compress (.Z) data")
+}
+
+impl<R: BufRead> Read for LzwDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pending.is_empty() && !self.done {
+            self.decode_next()?;
+        }
+
+        let count = buf.len().min(self.pending.len());
+        for byte in buf.iter_mut().take(count) {
+            *byte = self.pending.pop().expect("count is bounded by pending");
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::decompress_fixture;
+    use super::super::Encoding;
+    use anyhow::Result;
+
+    #[test]
+    fn decompress_lzw_test() -> Result<()> {
+        // `compress` output of 120 numbered lines, long enough for the code
+        // width to grow from 9 to 10 bits.
+        let buffer = [
+            0x1f, 0x9d, 0x90, 0x6c, 0xd2, 0xb8, 0x29, 0x3, 0x2, 0x86, 0x82, 0x80, 0x3, 0x41, 0xc4,
+            0x38, 0x28, 0x90, 0xa0, 0xc, 0x86, 0x9, 0x67, 0x40, 0x24, 0x48, 0x63, 0x22, 0x88, 0x1a,
+            0x16, 0x6d, 0x58, 0xbc, 0x61, 0x11, 0x87, 0xc5, 0x1c, 0x16, 0x63, 0x18, 0x44, 0x48,
+            0x30, 0xc6, 0x42, 0x92, 0xa, 0x1f, 0xa2, 0x8c, 0x21, 0x71, 0x65, 0xc5, 0x95, 0x18,
+            0x57, 0x6a, 0x5c, 0xc9, 0x71, 0xa5, 0xc7, 0x95, 0x20, 0x51, 0xca, 0x18, 0xd9, 0x10,
+            0x84, 0x8c, 0x93, 0x3d, 0x65, 0xa8, 0xc, 0xda, 0x32, 0xe8, 0xcb, 0xa0, 0x31, 0x83,
+            0xce, 0xc, 0x5a, 0x33, 0xe8, 0xcd, 0xa0, 0x39, 0x7b, 0xce, 0xe0, 0x19, 0x11, 0x68,
+            0xc4, 0xa1, 0x11, 0x8b, 0x46, 0x3c, 0x1a, 0x31, 0x69, 0xc4, 0xa5, 0x11, 0x9b, 0x46,
+            0x7c, 0x1a, 0x31, 0x6a, 0x42, 0x1a, 0x54, 0x29, 0x5a, 0xa5, 0x88, 0x95, 0xa2, 0x56,
+            0x8a, 0x5c, 0x29, 0x7a, 0xa5, 0x8, 0x96, 0xa2, 0x58, 0x8a, 0x64, 0x29, 0x9a, 0x25,
+            0x58, 0x23, 0xed, 0xc5, 0xb5, 0x17, 0xdb, 0x5e, 0x7c, 0x7b, 0x31, 0xee, 0xc5, 0xb9,
+            0x17, 0xeb, 0x5e, 0xbc, 0x7b, 0x31, 0xef, 0xc5, 0xbd, 0x20, 0x6c, 0xf8, 0xb5, 0x1,
+            0xd8, 0x86, 0x60, 0x1b, 0x84, 0x6d, 0x18, 0xb6, 0x81, 0xd8, 0x86, 0x62, 0x1b, 0x8c,
+            0x6d, 0x38, 0xb6, 0x1, 0xf9, 0x86, 0xdf, 0x1b, 0x80, 0x6f, 0x8, 0xbe, 0x41, 0xf8, 0x86,
+            0xe1, 0x1b, 0x88, 0x6f, 0x28, 0xbe, 0xc1, 0xf8, 0x86, 0xe3, 0x1b, 0x90, 0x71, 0xf8,
+            0xc5, 0x1, 0x18, 0x87, 0x60, 0x1c, 0x84, 0x71, 0x18, 0xc6, 0x81, 0x18, 0x87, 0x62,
+            0x1c, 0x8c, 0x71, 0x38, 0xc6, 0x1, 0x39, 0x87, 0xdf, 0x1c, 0x80, 0x73, 0x8, 0xce, 0x41,
+            0x38, 0x87, 0xe1, 0x1c, 0x88, 0x73, 0x28, 0xce, 0xc1, 0x38, 0x87, 0xe3, 0x1c, 0x90,
+            0x45, 0xfa, 0x15, 0x9, 0x58, 0xa4, 0x60, 0x91, 0x84, 0x45, 0x1a, 0x16, 0x89, 0x58,
+            0xa4, 0x62, 0x91, 0x8c, 0x45, 0x3a, 0x16, 0x19, 0x5e, 0x64, 0x48, 0x93, 0xf7, 0xcf,
+            0xb3, 0xbc, 0xbf, 0x3e, 0x46, 0xfb, 0x18, 0xef, 0xc5, 0x10, 0x5f, 0xc, 0xf3, 0xc5, 0x0,
+            0x12,
+        ];
+
+        let (encoding, got) = decompress_fixture(&buffer, "in.txt.Z")?;
+        let expected: String = (0..120).map(|i| format!("line {}\n", i)).collect();
+
+        assert_eq!(
+            encoding,
+            Encoding::Compressed {
+                codec: "lzw",
+                members: None
+            }
+        );
+        assert_eq!(got, expected);
+
+        Ok(())
+    }
+}
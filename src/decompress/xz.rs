@@ -0,0 +1,55 @@
+use anyhow::Result;
+use std::io::{BufRead, Read};
+
+use super::Decompressor;
+
+pub struct Xz;
+
+impl Decompressor for Xz {
+    fn name(&self) -> &'static str {
+        "xz"
+    }
+
+    fn magic(&self) -> &'static [u8] {
+        &[0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00]
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &[".xz"]
+    }
+
+    fn reader<'a>(&self, reader: Box<dyn BufRead + 'a>) -> Result<Box<dyn Read + 'a>> {
+        Ok(Box::new(xz2::bufread::XzDecoder::new_multi_decoder(reader)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::decompress_fixture;
+    use super::super::Encoding;
+    use anyhow::Result;
+
+    #[test]
+    fn decompress_xz_test() -> Result<()> {
+        let buffer = [
+            0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x0, 0x0, 0x4, 0xe6, 0xd6, 0xb4, 0x46, 0x4, 0xc0, 0x10,
+            0xc, 0x21, 0x1, 0x16, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x7b, 0xb0, 0x54,
+            0x28, 0x1, 0x0, 0xb, 0x48, 0x65, 0x6c, 0x6c, 0x6f, 0x20, 0x57, 0x6f, 0x72, 0x6c, 0x64,
+            0xa, 0x0, 0x22, 0xe0, 0x75, 0x3f, 0xd5, 0xed, 0x38, 0x3e, 0x0, 0x1, 0x2c, 0xc, 0xae,
+            0x92, 0x1, 0x10, 0x1f, 0xb6, 0xf3, 0x7d, 0x1, 0x0, 0x0, 0x0, 0x0, 0x4, 0x59, 0x5a,
+        ];
+
+        let (encoding, got) = decompress_fixture(&buffer, "in.txt.xz")?;
+
+        assert_eq!(
+            encoding,
+            Encoding::Compressed {
+                codec: "xz",
+                members: None
+            }
+        );
+        assert_eq!(got, "Hello World\n");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+use anyhow::Result;
+use std::io::{BufRead, Read};
+
+use super::Decompressor;
+
+pub struct Zstd;
+
+impl Decompressor for Zstd {
+    fn name(&self) -> &'static str {
+        "zstd"
+    }
+
+    fn magic(&self) -> &'static [u8] {
+        &[0x28, 0xb5, 0x2f, 0xfd]
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &[".zst", ".zstd"]
+    }
+
+    fn reader<'a>(&self, reader: Box<dyn BufRead + 'a>) -> Result<Box<dyn Read + 'a>> {
+        Ok(Box::new(::zstd::stream::read::Decoder::with_buffer(
+            reader,
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::decompress_fixture;
+    use super::super::Encoding;
+    use anyhow::Result;
+
+    #[test]
+    fn decompress_zstd_test() -> Result<()> {
+        let buffer = [
+            0x28, 0xb5, 0x2f, 0xfd, 0x24, 0xc, 0x61, 0x0, 0x0, 0x48, 0x65, 0x6c, 0x6c, 0x6f, 0x20,
+            0x57, 0x6f, 0x72, 0x6c, 0x64, 0xa, 0x93, 0x43, 0xf, 0x1a,
+        ];
+
+        let (encoding, got) = decompress_fixture(&buffer, "in.txt.zst")?;
+
+        assert_eq!(
+            encoding,
+            Encoding::Compressed {
+                codec: "zstd",
+                members: None
+            }
+        );
+        assert_eq!(got, "Hello World\n");
+
+        Ok(())
+    }
+}
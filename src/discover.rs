@@ -0,0 +1,254 @@
+use anyhow::{anyhow, Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::collections::HashSet;
+use std::fs::{metadata, read};
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::input::{is_archive, list_entries, ENTRY_SEPARATOR};
+
+/// How directory arguments are scanned for input files.
+#[derive(Debug, Clone)]
+pub struct DiscoverOptions {
+    /// Only files matching one of these patterns are picked up. An empty
+    /// set matches everything.
+    pub include: GlobSet,
+    /// Files matching one of these patterns are skipped.
+    pub exclude: GlobSet,
+    /// Deepest directory level to descend into, 1 being the files directly
+    /// inside the directory argument.
+    pub max_depth: Option<usize>,
+    pub follow_symlinks: bool,
+}
+
+pub fn build_globset(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder
+            .add(Glob::new(pattern).with_context(|| format!("Wrong glob pattern ({})", pattern))?);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Reads input paths from `path`, one per line or NUL-separated when `null`
+/// is set. Empty entries are ignored.
+pub fn read_files_from(path: &Path, null: bool) -> Result<Vec<String>> {
+    let content =
+        read(path).with_context(|| format!("Failed to read input list ({})", path.display()))?;
+    let separator = if null { b'\0' } else { b'\n' };
+
+    content
+        .split(|&byte| byte == separator)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            String::from_utf8(entry.to_vec())
+                .with_context(|| format!("Non UTF-8 path in input list ({})", path.display()))
+        })
+        .collect()
+}
+
+impl DiscoverOptions {
+    fn matches(&self, path: &Path) -> bool {
+        (self.include.is_empty() || self.include.is_match(path)) && !self.exclude.is_match(path)
+    }
+}
+
+/// Collects inputs in discovery order, dropping duplicates.
+#[derive(Default)]
+struct Inputs {
+    seen: HashSet<String>,
+    paths: Vec<String>,
+}
+
+impl Inputs {
+    fn push(&mut self, path: String) {
+        if self.seen.insert(path.clone()) {
+            self.paths.push(path);
+        }
+    }
+
+    /// Adds a file, or the entries of a bundle that pass the filters.
+    fn push_file(&mut self, path: String, options: &DiscoverOptions) -> Result<()> {
+        if !is_archive(&path) {
+            self.push(path);
+            return Ok(());
+        }
+
+        for entry in list_entries(&path)? {
+            if options.matches(Path::new(&entry.name)) {
+                self.push(format!("{}{}{}", path, ENTRY_SEPARATOR, entry.name));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Expands directory arguments into the files they contain and bundles into
+/// their entries. Plain file arguments are kept as they are; the include and
+/// exclude patterns apply to paths relative to the directory being scanned
+/// and to entry names. Bundles met while scanning are opened even when they
+/// do not match the include patterns. Duplicates are dropped.
+pub fn discover_inputs(inputs: &[String], options: &DiscoverOptions) -> Result<Vec<String>> {
+    let mut result = Inputs::default();
+
+    for input in inputs {
+        let is_dir = metadata(input)
+            .map(|metadata| metadata.is_dir())
+            .unwrap_or(false);
+
+        if !is_dir {
+            result.push_file(input.to_owned(), options)?;
+            continue;
+        }
+
+        let mut walker = WalkDir::new(input)
+            .follow_links(options.follow_symlinks)
+            .sort_by_file_name();
+        if let Some(max_depth) = options.max_depth {
+            walker = walker.max_depth(max_depth);
+        }
+
+        for entry in walker {
+            let entry = entry.with_context(|| format!("Failed to scan directory ({})", input))?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let path = entry
+                .path()
+                .to_str()
+                .ok_or_else(|| anyhow!("Non UTF-8 path ({})", entry.path().display()))?
+                .to_owned();
+            let relative = entry.path().strip_prefix(input).unwrap_or(entry.path());
+            let wanted = if is_archive(&path) {
+                !options.exclude.is_match(relative)
+            } else {
+                options.matches(relative)
+            };
+
+            if wanted {
+                result.push_file(path, options)?;
+            }
+        }
+    }
+
+    Ok(result.paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, write};
+
+    use super::{build_globset, discover_inputs, read_files_from, DiscoverOptions};
+    use anyhow::Result;
+
+    fn options(include: &[&str], exclude: &[&str], max_depth: Option<usize>) -> DiscoverOptions {
+        let include: Vec<String> = include.iter().map(|s| s.to_string()).collect();
+        let exclude: Vec<String> = exclude.iter().map(|s| s.to_string()).collect();
+        DiscoverOptions {
+            include: build_globset(&include).unwrap(),
+            exclude: build_globset(&exclude).unwrap(),
+            max_depth,
+            follow_symlinks: false,
+        }
+    }
+
+    #[test]
+    fn discover_directory_test() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().to_str().unwrap();
+        create_dir_all(dir.path().join("nginx"))?;
+        write(dir.path().join("syslog.1.gz"), b"")?;
+        write(dir.path().join("syslog.2.gz"), b"")?;
+        write(dir.path().join("nginx/access.log.1.gz"), b"")?;
+        write(dir.path().join("nginx/access.log.1.gz.bak"), b"")?;
+
+        let found = discover_inputs(&[root.to_owned()], &options(&["*.gz"], &[], None))?;
+        assert_eq!(
+            found,
+            vec![
+                format!("{}/nginx/access.log.1.gz", root),
+                format!("{}/syslog.1.gz", root),
+                format!("{}/syslog.2.gz", root),
+            ]
+        );
+
+        let found = discover_inputs(&[root.to_owned()], &options(&[], &["nginx/*"], Some(1)))?;
+        assert_eq!(
+            found,
+            vec![
+                format!("{}/syslog.1.gz", root),
+                format!("{}/syslog.2.gz", root),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn discover_explicit_file_test() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("app.log.1.gz");
+        write(&file, b"")?;
+        let file = file.to_str().unwrap().to_owned();
+
+        // Explicit files are taken as given, even when a filter would skip
+        // them, and are not repeated.
+        let found = discover_inputs(
+            &[file.clone(), file.clone()],
+            &options(&[], &["*.gz"], None),
+        )?;
+        assert_eq!(found, vec![file]);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn discover_archive_test() -> Result<()> {
+        use std::fs::File;
+        use std::io::Write;
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("bundle.zip");
+        let mut writer = zip::ZipWriter::new(File::create(&path)?);
+        for name in ["logs/app.log.1", "logs/app.log", "README"] {
+            writer.start_file(name, zip::write::FileOptions::default())?;
+            writer.write_all(b"line\n")?;
+        }
+        writer.finish()?;
+
+        let root = dir.path().to_str().unwrap();
+        let found = discover_inputs(&[root.to_owned()], &options(&["logs/*"], &[], None))?;
+        assert_eq!(
+            found,
+            vec![
+                format!("{}/bundle.zip!logs/app.log.1", root),
+                format!("{}/bundle.zip!logs/app.log", root),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_files_from_test() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let list = dir.path().join("list");
+
+        write(&list, b"a.log.1.gz\nb c.log.2.gz\n\n")?;
+        assert_eq!(
+            read_files_from(&list, false)?,
+            vec!["a.log.1.gz", "b c.log.2.gz"]
+        );
+
+        write(&list, b"a.log.1.gz\0with\nnewline.gz\0")?;
+        assert_eq!(
+            read_files_from(&list, true)?,
+            vec!["a.log.1.gz", "with\nnewline.gz"]
+        );
+
+        Ok(())
+    }
+}
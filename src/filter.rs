@@ -0,0 +1,349 @@
+use anyhow::{Context, Result};
+use chrono::Datelike;
+use regex::bytes::{RegexSet, RegexSetBuilder};
+use std::collections::VecDeque;
+use std::io;
+
+use crate::input::{modified, STDIO_PATH};
+use crate::lines::LineSink;
+use crate::sort::content_timestamp;
+use crate::timestamp::leading_timestamp;
+
+/// Times between which lines are kept, in milliseconds since the epoch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimeWindow {
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+}
+
+impl TimeWindow {
+    pub fn is_bounded(&self) -> bool {
+        self.since.is_some() || self.until.is_some()
+    }
+
+    pub fn contains(&self, timestamp: i64) -> bool {
+        self.since.is_none_or(|since| timestamp >= since)
+            && self.until.is_none_or(|until| timestamp <= until)
+    }
+
+    /// Returns false when no line of the input at `path` can be in the
+    /// window: the first timestamp a line starts with is after it, or it was
+    /// last modified before it. Standard input is not read ahead and always
+    /// may be, and so may an input that fails to read, for the merge to
+    /// report.
+    pub fn may_contain_file(&self, path: &str) -> bool {
+        if !self.is_bounded() || path == STDIO_PATH {
+            return true;
+        }
+
+        let Ok(modified) = modified(path) else {
+            return true;
+        };
+        if self
+            .since
+            .is_some_and(|since| modified.timestamp_millis() < since)
+        {
+            return false;
+        }
+        if let Some(until) = self.until {
+            if let Ok(Some(first)) = content_timestamp(path, modified.year(), leading_timestamp) {
+                return first <= until;
+            }
+        }
+        true
+    }
+}
+
+/// Which lines of the inputs make it into the output, and how many lines
+/// around them come along.
+#[derive(Debug)]
+pub struct LineFilter {
+    matches: Option<RegexSet>,
+    excludes: Option<RegexSet>,
+    window: TimeWindow,
+    before: usize,
+    after: usize,
+}
+
+/// How the patterns of a [`LineFilter`] are read.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PatternOptions {
+    pub ignore_case: bool,
+    /// Patterns are plain strings rather than regular expressions.
+    pub fixed_strings: bool,
+}
+
+fn regex_set(patterns: &[String], options: PatternOptions) -> Result<Option<RegexSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+
+    let patterns: Vec<String> = if options.fixed_strings {
+        patterns
+            .iter()
+            .map(|pattern| regex::escape(pattern))
+            .collect()
+    } else {
+        patterns.to_vec()
+    };
+    let set = RegexSetBuilder::new(&patterns)
+        .case_insensitive(options.ignore_case)
+        .build()
+        .with_context(|| format!("Invalid line pattern ({})", patterns.join(", ")))?;
+    Ok(Some(set))
+}
+
+impl LineFilter {
+    /// Keeps the lines matching any of `matches`, or all lines when there
+    /// are none, unless they match one of `excludes` or fall outside
+    /// `window`. Returns `None` when every line would be kept.
+    pub fn new(
+        matches: &[String],
+        excludes: &[String],
+        options: PatternOptions,
+        window: TimeWindow,
+        before: usize,
+        after: usize,
+    ) -> Result<Option<Self>> {
+        if matches.is_empty() && excludes.is_empty() && !window.is_bounded() {
+            return Ok(None);
+        }
+
+        Ok(Some(LineFilter {
+            matches: regex_set(matches, options)?,
+            excludes: regex_set(excludes, options)?,
+            window,
+            before,
+            after,
+        }))
+    }
+
+    pub fn window(&self) -> TimeWindow {
+        self.window
+    }
+
+    /// Returns true when `line` matches the patterns, context and time
+    /// aside.
+    pub fn is_match(&self, line: &[u8]) -> bool {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        self.matches.as_ref().is_none_or(|set| set.is_match(line))
+            && !self.excludes.as_ref().is_some_and(|set| set.is_match(line))
+    }
+}
+
+/// Sink that passes the selected lines and their context on to `sink`.
+pub struct Filtered<'a, S: LineSink> {
+    filter: &'a LineFilter,
+    sink: S,
+    /// Lines held back in case one of the next lines is selected.
+    before: VecDeque<(u64, Vec<u8>)>,
+    /// Lines still to pass on after the last selected one.
+    after: usize,
+    /// Year of syslog timestamps, which have none.
+    default_year: i32,
+    /// Whether the last timestamp seen was in the window. Lines not
+    /// starting with one, like those of a stack trace, go with the line
+    /// before them.
+    in_window: bool,
+}
+
+impl<'a, S: LineSink> Filtered<'a, S> {
+    pub fn new(filter: &'a LineFilter, sink: S, default_year: i32) -> Self {
+        Filtered {
+            filter,
+            sink,
+            before: VecDeque::with_capacity(filter.before),
+            after: 0,
+            default_year,
+            in_window: true,
+        }
+    }
+
+    fn is_selected(&mut self, line: &[u8]) -> bool {
+        if self.filter.window.is_bounded() {
+            let text = String::from_utf8_lossy(line);
+            if let Some(timestamp) = leading_timestamp(&text, self.default_year) {
+                self.in_window = self.filter.window.contains(timestamp);
+            }
+        }
+        self.in_window && self.filter.is_match(line)
+    }
+}
+
+impl<S: LineSink> LineSink for Filtered<'_, S> {
+    fn line(&mut self, number: u64, line: &[u8]) -> io::Result<()> {
+        if self.is_selected(line) {
+            for (number, line) in self.before.drain(..) {
+                self.sink.line(number, &line)?;
+            }
+            self.after = self.filter.after;
+            self.sink.line(number, line)
+        } else if self.after > 0 {
+            self.after -= 1;
+            self.sink.line(number, line)
+        } else {
+            if self.filter.before > 0 {
+                if self.before.len() == self.filter.before {
+                    self.before.pop_front();
+                }
+                self.before.push_back((number, line.to_vec()));
+            }
+            Ok(())
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.sink.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    use super::{Filtered, LineFilter, PatternOptions, TimeWindow};
+    use crate::lines::{LinePrefix, Lines};
+    use anyhow::Result;
+
+    fn filter(filter: &LineFilter, input: &str) -> Result<String> {
+        let mut output = Vec::new();
+        let mut lines = Lines::new(Filtered::new(
+            filter,
+            LinePrefix::new(&mut output, "app.log"),
+            2022,
+        ));
+        lines.write_all(input.as_bytes())?;
+        lines.finish()?;
+        drop(lines);
+        Ok(String::from_utf8(output)?)
+    }
+
+    #[test]
+    fn line_filter_test() -> Result<()> {
+        let input = "a info\nb ERROR disk\nc info\nd info\ne info\nf error net\ng debug";
+
+        let errors = LineFilter::new(
+            &[String::from("error")],
+            &[String::from("net")],
+            PatternOptions {
+                ignore_case: true,
+                fixed_strings: false,
+            },
+            TimeWindow::default(),
+            0,
+            0,
+        )?
+        .expect("filter");
+        assert_eq!(filter(&errors, input)?, "app.log:2:b ERROR disk\n");
+
+        // Context lines keep their own numbers and are not repeated.
+        let context = LineFilter::new(
+            &[String::from("ERROR"), String::from("g debug")],
+            &[],
+            PatternOptions {
+                ignore_case: false,
+                fixed_strings: true,
+            },
+            TimeWindow::default(),
+            1,
+            1,
+        )?
+        .expect("filter");
+        assert_eq!(
+            filter(&context, input)?,
+            "app.log:1:a info\napp.log:2:b ERROR disk\napp.log:3:c info\n\
+             app.log:6:f error net\napp.log:7:g debug"
+        );
+
+        // Fixed strings are not regular expressions.
+        let dots = LineFilter::new(
+            &[String::from("a.info")],
+            &[],
+            PatternOptions {
+                ignore_case: false,
+                fixed_strings: true,
+            },
+            TimeWindow::default(),
+            0,
+            0,
+        )?
+        .expect("filter");
+        assert_eq!(filter(&dots, input)?, "");
+
+        assert!(LineFilter::new(
+            &[],
+            &[],
+            PatternOptions::default(),
+            TimeWindow::default(),
+            2,
+            2
+        )?
+        .is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn time_window_test() -> Result<()> {
+        let input = "Jul 15 09:59:59 host app: early\n\
+                     Jul 15 10:00:00 host app: boom\n\
+                     \tat com.example.Main.run\n\
+                     Caused by: timeout at 2021-01-01T00:00:00Z\n\
+                     Jul 15 10:30:00 host app: later\n\
+                     Jul 15 11:00:01 host app: late\n\
+                     \tat com.example.Main.stop\n";
+        let window = LineFilter::new(
+            &[],
+            &[],
+            PatternOptions::default(),
+            TimeWindow {
+                // 2022-07-15T10:00:00Z to 11:00:00
+                since: Some(1_657_879_200_000),
+                until: Some(1_657_882_800_000),
+            },
+            0,
+            0,
+        )?
+        .expect("filter");
+
+        // Lines not starting with a timestamp go with the line before them.
+        assert_eq!(
+            filter(&window, input)?,
+            "app.log:2:Jul 15 10:00:00 host app: boom\n\
+             app.log:3:\tat com.example.Main.run\n\
+             app.log:4:Caused by: timeout at 2021-01-01T00:00:00Z\n\
+             app.log:5:Jul 15 10:30:00 host app: later\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn may_contain_file_test() -> Result<()> {
+        let dir = tempdir()?;
+        let window = TimeWindow {
+            since: None,
+            // 2022-07-15T10:00:00Z
+            until: Some(1_657_879_200_000),
+        };
+
+        let late = dir.path().join("late.log");
+        write(&late, "2022-07-15T11:00:00Z late\n")?;
+        assert!(!window.may_contain_file(&late.to_string_lossy()));
+
+        // A date further on in a line does not count.
+        let cert = dir.path().join("cert.log");
+        write(
+            &cert,
+            "starting, certificate expires 2030-01-01T00:00:00Z\n\
+             2022-07-15T09:00:00Z renewed\n",
+        )?;
+        assert!(window.may_contain_file(&cert.to_string_lossy()));
+
+        // A gzip header followed by garbage is left for the merge to report.
+        let corrupt = dir.path().join("corrupt.log.gz");
+        write(&corrupt, b"\x1f\x8b\x08\0\0\0\0\0\0\x03garbage")?;
+        assert!(window.may_contain_file(&corrupt.to_string_lossy()));
+        Ok(())
+    }
+}
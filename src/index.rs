@@ -0,0 +1,177 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::fs::metadata;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+use crate::json;
+use crate::report::FileReport;
+
+/// Format of the index written next to an output.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexFormat {
+    Json,
+    Csv,
+}
+
+impl IndexFormat {
+    /// Path of the index of `output`, like `merged.log.index.json`.
+    pub fn path(self, output: &Path) -> PathBuf {
+        let mut path = output.as_os_str().to_owned();
+        path.push(match self {
+            IndexFormat::Json => ".index.json",
+            IndexFormat::Csv => ".index.csv",
+        });
+        PathBuf::from(path)
+    }
+}
+
+/// Where the content of one input file ended up in the uncompressed output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Slice {
+    /// Offset of the first byte.
+    pub start: u64,
+    /// Offset just past the last byte.
+    pub end: u64,
+    /// Lines, counting a last line without newline.
+    pub lines: u64,
+    /// CRC32 of the bytes between `start` and `end`.
+    pub crc32: u32,
+}
+
+/// Quotes a CSV field when it needs it.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+fn write_entries(out: &mut dyn Write, format: IndexFormat, reports: &[FileReport]) -> Result<()> {
+    match format {
+        IndexFormat::Json => {
+            let entries: Vec<String> = reports
+                .iter()
+                .map(|report| {
+                    json::object(&[
+                        ("path", json::string(&report.path)),
+                        ("start", report.slice.start.to_string()),
+                        ("end", report.slice.end.to_string()),
+                        ("lines", report.slice.lines.to_string()),
+                        (
+                            "crc32",
+                            json::string(&format!("{:08x}", report.slice.crc32)),
+                        ),
+                    ])
+                })
+                .collect();
+            writeln!(out, "[{}]", entries.join(",\n"))?;
+        }
+        IndexFormat::Csv => {
+            writeln!(out, "path,start,end,lines,crc32")?;
+            for report in reports {
+                writeln!(
+                    out,
+                    "{},{},{},{},{:08x}",
+                    csv_field(&report.path),
+                    report.slice.start,
+                    report.slice.end,
+                    report.slice.lines,
+                    report.slice.crc32
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the index of the files merged into `output` next to it, replacing
+/// any previous index in one go.
+pub fn write_index(output: &Path, format: IndexFormat, reports: &[FileReport]) -> Result<()> {
+    let path = format.path(output);
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let temp = NamedTempFile::new_in(parent)
+        .with_context(|| format!("Failed to create index file ({})", path.display()))?;
+
+    // Temporary files are private, the index is as readable as the output.
+    let permissions = metadata(output)
+        .with_context(|| format!("Failed to read output file ({})", output.display()))?
+        .permissions();
+    temp.as_file().set_permissions(permissions)?;
+
+    let mut writer = BufWriter::new(temp);
+    write_entries(&mut writer, format, reports)?;
+    let temp = writer.into_inner().map_err(|error| error.into_error())?;
+    temp.persist(&path)
+        .with_context(|| format!("Failed to write index file ({})", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{read_to_string, write};
+
+    use super::{write_entries, write_index, IndexFormat, Slice};
+    use crate::decompress::Encoding;
+    use crate::report::FileReport;
+    use anyhow::Result;
+
+    #[test]
+    fn write_entries_test() -> Result<()> {
+        let reports = vec![FileReport {
+            path: String::from("logs, old/app.log.2.gz"),
+            bytes: 12,
+            result: Ok(Encoding::Plain),
+            damage: Vec::new(),
+            slice: Slice {
+                start: 0,
+                end: 12,
+                lines: 1,
+                crc32: 0xb095e5e3,
+            },
+        }];
+
+        let mut csv = Vec::new();
+        write_entries(&mut csv, IndexFormat::Csv, &reports)?;
+        assert_eq!(
+            String::from_utf8(csv)?,
+            "path,start,end,lines,crc32\n\"logs, old/app.log.2.gz\",0,12,1,b095e5e3\n"
+        );
+
+        let mut json = Vec::new();
+        write_entries(&mut json, IndexFormat::Json, &reports)?;
+        assert_eq!(
+            String::from_utf8(json)?,
+            "[{\"path\":\"logs, old/app.log.2.gz\",\"start\":0,\"end\":12,\"lines\":1,\"crc32\":\"b095e5e3\"}]\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn write_index_test() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("merged.log");
+        write(&output, "")?;
+        #[cfg(unix)]
+        {
+            use std::fs::{set_permissions, Permissions};
+            use std::os::unix::fs::PermissionsExt;
+            set_permissions(&output, Permissions::from_mode(0o640))?;
+        }
+        write_index(&output, IndexFormat::Csv, &[])?;
+
+        let path = dir.path().join("merged.log.index.csv");
+        assert_eq!(read_to_string(&path)?, "path,start,end,lines,crc32\n");
+        assert_eq!(
+            path.metadata()?.permissions(),
+            output.metadata()?.permissions()
+        );
+        Ok(())
+    }
+}
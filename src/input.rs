@@ -0,0 +1,537 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::fs::{metadata, File};
+use std::io::{stdin, BufRead, BufReader};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+#[cfg(feature = "tar")]
+use crate::decompress::decoded_reader;
+
+/// Input path, and output path, standing for the standard streams.
+pub const STDIO_PATH: &str = "-";
+
+/// Separates the path of a bundle from the name of an entry inside it, as in
+/// `logs.tar.gz!var/log/app.log.1.gz`.
+pub const ENTRY_SEPARATOR: char = '!';
+
+/// Bundles whose entries are read in place. They are recognized even when
+/// the matching cargo feature is off, so that a slim build reports them
+/// instead of reading them as logs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveKind {
+    Tar,
+    Zip,
+}
+
+/// Compression extensions a tarball may carry after `.tar`.
+const TAR_COMPRESSIONS: [&str; 7] = ["", ".gz", ".zst", ".xz", ".bz2", ".lz4", ".Z"];
+
+fn archive_kind(path: &str) -> Option<ArchiveKind> {
+    if path.ends_with(".zip") {
+        Some(ArchiveKind::Zip)
+    } else if path.ends_with(".tgz")
+        || TAR_COMPRESSIONS
+            .iter()
+            .any(|extension| path.ends_with(&format!(".tar{}", extension)))
+    {
+        Some(ArchiveKind::Tar)
+    } else {
+        None
+    }
+}
+
+/// Returns true when `path` names a bundle whose entries should be listed
+/// instead of reading it as a log.
+pub fn is_archive(path: &str) -> bool {
+    archive_kind(path).is_some()
+}
+
+/// Splits an input path into the bundle and the entry name, or returns
+/// `None` for a plain file.
+pub fn split_entry_path(path: &str) -> Option<(&str, &str)> {
+    path.match_indices(ENTRY_SEPARATOR)
+        .map(|(index, _)| (&path[..index], &path[index + 1..]))
+        .find(|(archive, _)| is_archive(archive))
+}
+
+/// A regular file stored in a bundle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    pub size: u64,
+    pub modified: Option<DateTime<Utc>>,
+}
+
+/// Lists the regular files stored in the bundle at `path`, in archive
+/// order. Each bundle is only listed once per run.
+pub fn list_entries(path: &str) -> Result<Vec<Entry>> {
+    static LISTS: OnceLock<Mutex<HashMap<String, Vec<Entry>>>> = OnceLock::new();
+    let mut lists = LISTS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    if let Some(entries) = lists.get(path) {
+        return Ok(entries.clone());
+    }
+
+    let entries = match archive_kind(path) {
+        Some(ArchiveKind::Tar) => tarball::list_entries(path),
+        Some(ArchiveKind::Zip) => zipfile::list_entries(path),
+        None => bail!("Not an archive ({})", path),
+    };
+    let entries = entries.with_context(|| format!("Failed to list archive ({})", path))?;
+    lists.insert(path.to_owned(), entries.clone());
+    Ok(entries)
+}
+
+/// Opens the input at `path`, which is either a file on disk, an entry
+/// inside a bundle or `-` for standard input, and hands a reader over its
+/// raw content to `f`. Entries are read out of the bundle without being
+/// extracted.
+pub fn with_input<T>(path: &str, f: impl FnOnce(&mut dyn BufRead) -> Result<T>) -> Result<T> {
+    match split_entry_path(path) {
+        Some((archive, entry)) => match archive_kind(archive) {
+            Some(ArchiveKind::Tar) => tarball::with_entry(archive, entry, f),
+            Some(ArchiveKind::Zip) => zipfile::with_entry(archive, entry, f),
+            None => unreachable!("split_entry_path only accepts archives"),
+        },
+        None if path == STDIO_PATH => f(&mut stdin().lock()),
+        None => {
+            let file = File::open(path)
+                .with_context(|| format!("Failed to open archive file ({})", path))?;
+            f(&mut BufReader::new(file))
+        }
+    }
+}
+
+/// Modification time of the input at `path`, taken from the bundle for
+/// entries. Standard input is as new as it gets.
+pub fn modified(path: &str) -> Result<DateTime<Utc>> {
+    if path == STDIO_PATH {
+        return Ok(Utc::now());
+    }
+
+    if let Some((archive, name)) = split_entry_path(path) {
+        let entry = list_entries(archive)?
+            .into_iter()
+            .find(|entry| entry.name == name)
+            .with_context(|| format!("No such entry in archive ({})", path))?;
+        if let Some(modified) = entry.modified {
+            return Ok(modified);
+        }
+        return modified(archive);
+    }
+
+    let modified = metadata(Path::new(path))
+        .and_then(|metadata| metadata.modified())
+        .with_context(|| format!("Failed to read modification time ({})", path))?;
+    Ok(modified.into())
+}
+
+#[cfg(feature = "tar")]
+mod tarball {
+    use anyhow::{bail, Context, Result};
+    use chrono::{TimeZone, Utc};
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::fs::File;
+    use std::io::{copy, sink, BufRead, BufReader, Read, Seek, SeekFrom};
+    use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+    use tar::Archive;
+
+    use super::{decoded_reader, Entry};
+
+    fn open(path: &str) -> Result<Box<dyn Read>> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open archive file ({})", path))?;
+        decoded_reader(BufReader::new(file), path)
+    }
+
+    /// Regular files of a tarball with where their content starts in the
+    /// uncompressed archive, in archive order.
+    type Index = Vec<(Entry, u64)>;
+
+    fn build_index(path: &str) -> Result<Index> {
+        let mut archive = Archive::new(open(path)?);
+        let mut entries = Vec::new();
+        for entry in archive.entries()? {
+            let entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+
+            let name = entry.path()?.to_string_lossy().into_owned();
+            let modified = entry
+                .header()
+                .mtime()
+                .ok()
+                .and_then(|mtime| Utc.timestamp_opt(mtime as i64, 0).single());
+            let entry_info = Entry {
+                name,
+                size: entry.size(),
+                modified,
+            };
+            entries.push((entry_info, entry.raw_file_position()));
+        }
+
+        Ok(entries)
+    }
+
+    /// Index of the tarball at `path`, built on first use.
+    fn index(path: &str) -> Result<Arc<Index>> {
+        static INDEXES: OnceLock<Mutex<HashMap<String, Arc<Index>>>> = OnceLock::new();
+        let mut indexes = INDEXES
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(index) = indexes.get(path) {
+            return Ok(Arc::clone(index));
+        }
+        let index = Arc::new(build_index(path)?);
+        indexes.insert(path.to_owned(), Arc::clone(&index));
+        Ok(index)
+    }
+
+    pub fn list_entries(path: &str) -> Result<Vec<Entry>> {
+        Ok(index(path)?
+            .iter()
+            .map(|(entry, _)| entry.clone())
+            .collect())
+    }
+
+    /// A compressed tarball being read, `offset` bytes into the uncompressed
+    /// archive.
+    struct Stream {
+        reader: Box<dyn Read>,
+        offset: u64,
+    }
+
+    thread_local! {
+        static STREAMS: RefCell<HashMap<String, Stream>> = RefCell::new(HashMap::new());
+    }
+
+    /// Plain tarballs are read in place. Compressed ones cannot seek, so
+    /// their stream is kept open after an entry for the entries after it,
+    /// and only read again from the start for an entry behind it.
+    pub fn with_entry<T>(
+        path: &str,
+        name: &str,
+        f: impl FnOnce(&mut dyn BufRead) -> Result<T>,
+    ) -> Result<T> {
+        let index = index(path)?;
+        let Some((entry, position)) = index.iter().find(|(entry, _)| entry.name == name) else {
+            bail!("No such entry in archive ({}!{})", path, name)
+        };
+
+        if path.ends_with(".tar") {
+            let mut file = File::open(path)
+                .with_context(|| format!("Failed to open archive file ({})", path))?;
+            file.seek(SeekFrom::Start(*position))?;
+            return f(&mut BufReader::new(file.take(entry.size)));
+        }
+
+        let stream = STREAMS.with(|streams| streams.borrow_mut().remove(path));
+        let mut stream = match stream {
+            Some(stream) if stream.offset <= *position => stream,
+            _ => Stream {
+                reader: open(path)?,
+                offset: 0,
+            },
+        };
+        let skip = position - stream.offset;
+        if copy(&mut (&mut stream.reader).take(skip), &mut sink())? < skip {
+            bail!("Unexpected end of archive ({})", path);
+        }
+
+        let mut content = BufReader::new((&mut stream.reader).take(entry.size));
+        let result = f(&mut content);
+        // Whatever `f` left of the entry is skipped for the next one.
+        let mut rest = content.into_inner();
+        if copy(&mut rest, &mut sink()).is_ok() && rest.limit() == 0 {
+            stream.offset = position + entry.size;
+            STREAMS.with(|streams| streams.borrow_mut().insert(path.to_owned(), stream));
+        }
+        result
+    }
+}
+
+/// Stand-ins for bundle formats left out of the build.
+#[cfg(any(not(feature = "tar"), not(feature = "zip")))]
+mod disabled {
+    use anyhow::{bail, Result};
+    use std::io::BufRead;
+
+    use super::Entry;
+
+    pub fn list_entries(path: &str, feature: &str) -> Result<Vec<Entry>> {
+        bail!(
+            "{} is a bundle, but this build has no `{}` feature",
+            path,
+            feature
+        )
+    }
+
+    pub fn with_entry<T>(
+        path: &str,
+        feature: &str,
+        _f: impl FnOnce(&mut dyn BufRead) -> Result<T>,
+    ) -> Result<T> {
+        bail!(
+            "{} is a bundle, but this build has no `{}` feature",
+            path,
+            feature
+        )
+    }
+}
+
+#[cfg(not(feature = "tar"))]
+mod tarball {
+    use anyhow::Result;
+    use std::io::BufRead;
+
+    use super::{disabled, Entry};
+
+    pub fn list_entries(path: &str) -> Result<Vec<Entry>> {
+        disabled::list_entries(path, "tar")
+    }
+
+    pub fn with_entry<T>(
+        path: &str,
+        _name: &str,
+        f: impl FnOnce(&mut dyn BufRead) -> Result<T>,
+    ) -> Result<T> {
+        disabled::with_entry(path, "tar", f)
+    }
+}
+
+#[cfg(not(feature = "zip"))]
+mod zipfile {
+    use anyhow::Result;
+    use std::io::BufRead;
+
+    use super::{disabled, Entry};
+
+    pub fn list_entries(path: &str) -> Result<Vec<Entry>> {
+        disabled::list_entries(path, "zip")
+    }
+
+    pub fn with_entry<T>(
+        path: &str,
+        _name: &str,
+        f: impl FnOnce(&mut dyn BufRead) -> Result<T>,
+    ) -> Result<T> {
+        disabled::with_entry(path, "zip", f)
+    }
+}
+
+#[cfg(feature = "zip")]
+mod zipfile {
+    use anyhow::{Context, Result};
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use std::fs::File;
+    use std::io::{BufRead, BufReader};
+    use zip::ZipArchive;
+
+    use super::Entry;
+
+    fn open(path: &str) -> Result<ZipArchive<BufReader<File>>> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open archive file ({})", path))?;
+        Ok(ZipArchive::new(BufReader::new(file))?)
+    }
+
+    pub fn list_entries(path: &str) -> Result<Vec<Entry>> {
+        let mut archive = open(path)?;
+        let mut result = Vec::new();
+
+        for index in 0..archive.len() {
+            let file = archive.by_index(index)?;
+            if !file.is_file() {
+                continue;
+            }
+
+            // Zip stores local time without a zone, take it as UTC.
+            let time = file.last_modified();
+            let modified = NaiveDate::from_ymd_opt(
+                i32::from(time.year()),
+                u32::from(time.month()),
+                u32::from(time.day()),
+            )
+            .and_then(|date| {
+                date.and_hms_opt(
+                    u32::from(time.hour()),
+                    u32::from(time.minute()),
+                    u32::from(time.second()),
+                )
+            })
+            .map(|time| Utc.from_utc_datetime(&time));
+
+            result.push(Entry {
+                name: file.name().to_owned(),
+                size: file.size(),
+                modified,
+            });
+        }
+
+        Ok(result)
+    }
+
+    pub fn with_entry<T>(
+        path: &str,
+        name: &str,
+        f: impl FnOnce(&mut dyn BufRead) -> Result<T>,
+    ) -> Result<T> {
+        let mut archive = open(path)?;
+        let file = archive
+            .by_name(name)
+            .with_context(|| format!("No such entry in archive ({}!{})", path, name))?;
+        let mut reader = BufReader::new(file);
+        f(&mut reader)
+    }
+}
+
+#[cfg(all(test, feature = "tar", feature = "zip"))]
+mod tests {
+    use std::fs::File;
+    use std::io::Write;
+
+    use super::{list_entries, modified, split_entry_path, with_input};
+    use anyhow::Result;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    fn gzip(content: &[u8]) -> Result<Vec<u8>> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(content)?;
+        Ok(encoder.finish()?)
+    }
+
+    fn read_input(path: &str) -> Result<Vec<u8>> {
+        with_input(path, |reader| {
+            let mut content = Vec::new();
+            reader.read_to_end(&mut content)?;
+            Ok(content)
+        })
+    }
+
+    #[test]
+    fn split_entry_path_test() {
+        assert_eq!(
+            split_entry_path("logs.tar.gz!var/log/app.log.1.gz"),
+            Some(("logs.tar.gz", "var/log/app.log.1.gz"))
+        );
+        assert_eq!(split_entry_path("/var/log/app!.log.1.gz"), None);
+    }
+
+    #[test]
+    fn tar_gz_test() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("logs.tar.gz");
+
+        let encoder = GzEncoder::new(File::create(&path)?, Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        for (name, content) in [
+            ("logs/app.log.1.gz", gzip(b"one\n")?),
+            ("logs/app.log", b"live\n".to_vec()),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(1657843200);
+            header.set_cksum();
+            builder.append_data(&mut header, name, content.as_slice())?;
+        }
+        builder.into_inner()?.finish()?;
+
+        let path = path.to_str().unwrap();
+        let entries = list_entries(path)?;
+        let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, vec!["logs/app.log.1.gz", "logs/app.log"]);
+        assert_eq!(entries[1].size, 5);
+
+        assert_eq!(read_input(&format!("{}!logs/app.log", path))?, b"live\n");
+        assert_eq!(
+            read_input(&format!("{}!logs/app.log.1.gz", path))?,
+            gzip(b"one\n")?
+        );
+        assert!(read_input(&format!("{}!logs/missing.log", path)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn tar_test() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        for name in ["logs.tar", "logs.tar.gz"] {
+            let path = dir.path().join(name);
+            let file = File::create(&path)?;
+            let writer: Box<dyn Write> = if name.ends_with(".gz") {
+                Box::new(GzEncoder::new(file, Compression::default()))
+            } else {
+                Box::new(file)
+            };
+
+            let mut builder = tar::Builder::new(writer);
+            for (index, name) in ["app.log", "app.log.1", "app.log.2"].iter().enumerate() {
+                let content = format!("{}\n", name);
+                let mut header = tar::Header::new_gnu();
+                header.set_size(content.len() as u64);
+                header.set_mode(0o644);
+                header.set_mtime(1657843200 - index as u64 * 86400);
+                header.set_cksum();
+                builder.append_data(&mut header, name, content.as_bytes())?;
+            }
+            builder.into_inner()?.flush()?;
+
+            // Entries are read in merge order, against the archive order,
+            // then in archive order.
+            let path = path.to_str().unwrap();
+            for name in [
+                "app.log.2",
+                "app.log.1",
+                "app.log",
+                "app.log.1",
+                "app.log.2",
+            ] {
+                let entry = format!("{}!{}", path, name);
+                assert_eq!(read_input(&entry)?, format!("{}\n", name).into_bytes());
+            }
+            assert_eq!(
+                modified(&format!("{}!app.log.2", path))?.timestamp(),
+                1657843200 - 2 * 86400
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn zip_test() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("logs.zip");
+
+        let mut writer = zip::ZipWriter::new(File::create(&path)?);
+        let options =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        writer.add_directory("logs/", options)?;
+        writer.start_file("logs/app.log.1", options)?;
+        writer.write_all(b"one\n")?;
+        writer.start_file("logs/app.log", options)?;
+        writer.write_all(b"live\n")?;
+        writer.finish()?;
+
+        let path = path.to_str().unwrap();
+        let names: Vec<String> = list_entries(path)?
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(names, vec!["logs/app.log.1", "logs/app.log"]);
+
+        assert_eq!(read_input(&format!("{}!logs/app.log.1", path))?, b"one\n");
+
+        Ok(())
+    }
+}
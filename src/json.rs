@@ -0,0 +1,50 @@
+/// Quotes `value` as a JSON string.
+pub fn string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Formats an optional value with `format`, or `null`.
+pub fn optional<T>(value: Option<T>, format: impl FnOnce(T) -> String) -> String {
+    value.map_or_else(|| String::from("null"), format)
+}
+
+/// Builds a JSON object out of already formatted values.
+pub fn object(fields: &[(&str, String)]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(name, value)| format!("{}:{}", string(name), value))
+        .collect();
+    format!("{{{}}}", fields.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{object, optional, string};
+
+    #[test]
+    fn json_test() {
+        assert_eq!(string("a \"b\"\\\n\u{1}"), r#""a \"b\"\\\n\u0001""#);
+        assert_eq!(
+            object(&[
+                ("path", string("app.log")),
+                ("size", optional(Some(3), |size: u64| size.to_string())),
+                ("mtime", optional(None, |mtime: u32| mtime.to_string())),
+            ]),
+            r#"{"path":"app.log","size":3,"mtime":null}"#
+        );
+    }
+}
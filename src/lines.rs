@@ -0,0 +1,307 @@
+use regex::bytes::Regex;
+use std::io::{self, Write};
+
+use crate::timestamp::starts_with_timestamp;
+
+/// Receives the lines of one source file, numbered from 1. Every line keeps
+/// its newline, except maybe the last one. Behind [`Records`] a line is a
+/// whole record, numbered after its first line.
+pub trait LineSink {
+    fn line(&mut self, number: u64, line: &[u8]) -> io::Result<()>;
+
+    /// Called after the last line of the file.
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: Write + ?Sized> LineSink for &mut W {
+    fn line(&mut self, _number: u64, line: &[u8]) -> io::Result<()> {
+        self.write_all(line)
+    }
+}
+
+impl<S: LineSink + ?Sized> LineSink for Box<S> {
+    fn line(&mut self, number: u64, line: &[u8]) -> io::Result<()> {
+        (**self).line(number, line)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        (**self).finish()
+    }
+}
+
+/// Writer that cuts what is written to it into lines for a [`LineSink`].
+/// A line split across writes is held back until its newline arrives, or
+/// until [`Lines::finish`].
+pub struct Lines<S: LineSink> {
+    sink: S,
+    number: u64,
+    partial: Vec<u8>,
+}
+
+impl<S: LineSink> Lines<S> {
+    pub fn new(sink: S) -> Self {
+        Lines {
+            sink,
+            number: 0,
+            partial: Vec::new(),
+        }
+    }
+
+    /// Hands over a last line without newline and ends the file.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if !self.partial.is_empty() {
+            self.number += 1;
+            self.sink.line(self.number, &self.partial)?;
+            self.partial.clear();
+        }
+        self.sink.finish()
+    }
+}
+
+impl<S: LineSink> Write for Lines<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut rest = buf;
+        while let Some(newline) = rest.iter().position(|&byte| byte == b'\n') {
+            let (line, next) = rest.split_at(newline + 1);
+            self.number += 1;
+            if self.partial.is_empty() {
+                self.sink.line(self.number, line)?;
+            } else {
+                self.partial.extend_from_slice(line);
+                self.sink.line(self.number, &self.partial)?;
+                self.partial.clear();
+            }
+            rest = next;
+        }
+        self.partial.extend_from_slice(rest);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// How to tell the first line of a record from its continuation lines.
+#[derive(Debug, Clone)]
+pub enum RecordStart {
+    /// Records start with a line matching the regex.
+    Pattern(Regex),
+    /// Records start with a line starting with a timestamp.
+    Timestamp,
+}
+
+impl RecordStart {
+    fn is_start(&self, line: &[u8]) -> bool {
+        match self {
+            RecordStart::Pattern(regex) => regex.is_match(line),
+            RecordStart::Timestamp => starts_with_timestamp(&String::from_utf8_lossy(line)),
+        }
+    }
+}
+
+/// Most lines a record holds before it is passed on in pieces.
+const MAX_RECORD_LINES: usize = 1000;
+/// Most bytes a record holds before it is passed on in pieces.
+const MAX_RECORD_BYTES: usize = 1 << 20;
+
+/// Sink that joins continuation lines, like those of a stack trace, to the
+/// line starting their record and passes on whole records. Lines before the
+/// first start of a record are passed on one by one, so that input without
+/// any is left as it is. A record growing past [`MAX_RECORD_LINES`] or
+/// [`MAX_RECORD_BYTES`] is passed on as it stands and the rest of it makes
+/// up another one.
+pub struct Records<'a, S: LineSink> {
+    start: &'a RecordStart,
+    sink: S,
+    /// A record has started in the file.
+    started: bool,
+    /// Number of the first line of `record`.
+    number: u64,
+    record: Vec<u8>,
+    lines: usize,
+}
+
+impl<'a, S: LineSink> Records<'a, S> {
+    pub fn new(start: &'a RecordStart, sink: S) -> Self {
+        Records {
+            start,
+            sink,
+            started: false,
+            number: 0,
+            record: Vec::new(),
+            lines: 0,
+        }
+    }
+
+    fn flush_record(&mut self) -> io::Result<()> {
+        if !self.record.is_empty() {
+            self.sink.line(self.number, &self.record)?;
+            self.record.clear();
+            self.lines = 0;
+        }
+        Ok(())
+    }
+}
+
+impl<S: LineSink> LineSink for Records<'_, S> {
+    fn line(&mut self, number: u64, line: &[u8]) -> io::Result<()> {
+        if self.start.is_start(line) {
+            self.flush_record()?;
+            self.started = true;
+        } else if !self.started {
+            return self.sink.line(number, line);
+        }
+
+        if self.record.is_empty() {
+            self.number = number;
+        }
+        self.record.extend_from_slice(line);
+        self.lines += 1;
+        if self.lines >= MAX_RECORD_LINES || self.record.len() >= MAX_RECORD_BYTES {
+            self.flush_record()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.flush_record()?;
+        self.sink.finish()
+    }
+}
+
+/// Sink that starts every line, or every record, with `path:number:`.
+pub struct LinePrefix<'a, W: Write + ?Sized> {
+    inner: &'a mut W,
+    path: &'a str,
+}
+
+impl<'a, W: Write + ?Sized> LinePrefix<'a, W> {
+    pub fn new(inner: &'a mut W, path: &'a str) -> Self {
+        LinePrefix { inner, path }
+    }
+}
+
+impl<W: Write + ?Sized> LineSink for LinePrefix<'_, W> {
+    fn line(&mut self, number: u64, line: &[u8]) -> io::Result<()> {
+        write!(self.inner, "{}:{}:", self.path, number)?;
+        self.inner.write_all(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::{LinePrefix, LineSink, Lines, RecordStart, Records, MAX_RECORD_LINES};
+    use anyhow::Result;
+    use regex::bytes::Regex;
+
+    #[test]
+    fn line_prefix_test() -> Result<()> {
+        let mut merged = Vec::new();
+        let mut writer = Lines::new(LinePrefix::new(&mut merged, "app.log.1.gz"));
+        // Lines split across writes keep a single prefix.
+        writer.write_all(b"first\nsec")?;
+        writer.write_all(b"ond\n\nlast")?;
+        writer.finish()?;
+
+        assert_eq!(
+            String::from_utf8(merged)?,
+            "app.log.1.gz:1:first\napp.log.1.gz:2:second\napp.log.1.gz:3:\napp.log.1.gz:4:last"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn records_test() -> Result<()> {
+        let input = "note\n\
+                     2022-07-15T10:00:01Z ERROR boom\n\
+                     java.lang.IllegalStateException: boom\n\
+                     \tat com.example.Main.run(Main.java:42)\n\
+                     2022-07-15T10:00:02Z INFO done";
+
+        let mut merged = Vec::new();
+        let start = RecordStart::Timestamp;
+        let mut writer = Lines::new(Records::new(
+            &start,
+            LinePrefix::new(&mut merged, "app.log"),
+        ));
+        writer.write_all(input.as_bytes())?;
+        writer.finish()?;
+        drop(writer);
+        assert_eq!(
+            String::from_utf8(merged)?,
+            "app.log:1:note\n\
+             app.log:2:2022-07-15T10:00:01Z ERROR boom\n\
+             java.lang.IllegalStateException: boom\n\
+             \tat com.example.Main.run(Main.java:42)\n\
+             app.log:5:2022-07-15T10:00:02Z INFO done"
+        );
+
+        let mut merged = Vec::new();
+        let start = RecordStart::Pattern(Regex::new(r"^\S")?);
+        let mut writer = Lines::new(Records::new(
+            &start,
+            LinePrefix::new(&mut merged, "app.log"),
+        ));
+        writer.write_all(b"Traceback:\n  File \"a.py\"\nKeyError: 'x'\n")?;
+        writer.finish()?;
+        drop(writer);
+        assert_eq!(
+            String::from_utf8(merged)?,
+            "app.log:1:Traceback:\n  File \"a.py\"\napp.log:3:KeyError: 'x'\n"
+        );
+        Ok(())
+    }
+
+    /// Collects what a [`Records`] passes on.
+    #[derive(Default)]
+    struct Collect(Vec<(u64, String)>);
+
+    impl LineSink for &mut Collect {
+        fn line(&mut self, number: u64, line: &[u8]) -> std::io::Result<()> {
+            self.0
+                .push((number, String::from_utf8_lossy(line).into_owned()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn records_without_starts_test() -> Result<()> {
+        // logfmt has no timestamp at the start of its lines.
+        let input = "level=info msg=started\n\
+                     level=error msg=boom\n\
+                     level=info msg=stopped\n";
+        let mut collect = Collect::default();
+        let start = RecordStart::Timestamp;
+        let mut writer = Lines::new(Records::new(&start, &mut collect));
+        writer.write_all(input.as_bytes())?;
+        writer.finish()?;
+        drop(writer);
+        assert_eq!(
+            collect.0,
+            [
+                (1, String::from("level=info msg=started\n")),
+                (2, String::from("level=error msg=boom\n")),
+                (3, String::from("level=info msg=stopped\n")),
+            ]
+        );
+
+        // A record without end is cut at the limit.
+        let mut collect = Collect::default();
+        let mut writer = Lines::new(Records::new(&start, &mut collect));
+        writer.write_all(b"2022-07-15T10:00:01Z ERROR boom\n")?;
+        for _ in 0..MAX_RECORD_LINES {
+            writer.write_all(b"\tat com.example.Main.run\n")?;
+        }
+        writer.finish()?;
+        drop(writer);
+        let numbers: Vec<u64> = collect.0.iter().map(|(number, _)| *number).collect();
+        assert_eq!(numbers, [1, MAX_RECORD_LINES as u64 + 1]);
+        Ok(())
+    }
+}
@@ -0,0 +1,258 @@
+use anyhow::Result;
+use chrono::{TimeZone, Utc};
+use clap::ValueEnum;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs::{metadata, File};
+use std::io::{self, stdout, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::decompress::{gzip_header, is_gzip, os_name, GzipHeader};
+use crate::input::{list_entries, split_entry_path, with_input, STDIO_PATH};
+use crate::json;
+use crate::report::{write_table, Align};
+use crate::sort::{parse_rotation, SortKey};
+
+/// How `list` prints the planned merge.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ListFormat {
+    Table,
+    Json,
+}
+
+/// One input file of the planned merge.
+#[derive(Debug)]
+pub struct Listed {
+    /// Output the file goes to, when planning a merge.
+    pub output: Option<String>,
+    /// Position in the merge order of its output, starting at 1.
+    pub position: usize,
+    pub path: String,
+    /// Position in the rotation as parsed from the name.
+    pub rotation: SortKey,
+    pub header: Option<GzipHeader>,
+    /// Size of the file as stored, unknown for standard input.
+    pub compressed: Option<u64>,
+    /// ISIZE trailer of the last gzip member. It is the uncompressed size
+    /// modulo 2^32 of single member files, and only known for files on disk.
+    pub isize: Option<u32>,
+}
+
+/// Reads the last four bytes of the gzip file at `path`.
+fn read_isize(path: &str) -> Result<u32> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::End(-4))?;
+    let mut trailer = [0u8; 4];
+    file.read_exact(&mut trailer)?;
+    Ok(u32::from_le_bytes(trailer))
+}
+
+/// Gathers what `list` shows about the files of every job, in merge order.
+/// Standard input is listed without being read.
+pub fn list_files(
+    jobs: &[(Option<&Path>, &[String])],
+    pattern: Option<&Regex>,
+) -> Result<Vec<Listed>> {
+    // Bundles are listed once however many entries they hold.
+    let mut entry_sizes: HashMap<String, HashMap<String, u64>> = HashMap::new();
+    let mut listed = Vec::new();
+
+    for (output, files) in jobs {
+        for (position, path) in files.iter().enumerate() {
+            let rotation = parse_rotation(path, pattern)?.key;
+
+            let (header, compressed, isize) = if path == STDIO_PATH {
+                (None, None, None)
+            } else if let Some((archive, name)) = split_entry_path(path) {
+                if !entry_sizes.contains_key(archive) {
+                    let sizes = list_entries(archive)?
+                        .into_iter()
+                        .map(|entry| (entry.name, entry.size))
+                        .collect();
+                    entry_sizes.insert(archive.to_owned(), sizes);
+                }
+                let size = entry_sizes[archive].get(name).copied();
+                (with_input(path, |reader| gzip_header(reader))?, size, None)
+            } else {
+                let (header, gzip) = with_input(path, |reader| {
+                    let gzip = is_gzip(reader.fill_buf()?);
+                    Ok((gzip_header(reader)?, gzip))
+                })?;
+                let isize = if gzip { Some(read_isize(path)?) } else { None };
+                (header, Some(metadata(path)?.len()), isize)
+            };
+
+            listed.push(Listed {
+                output: output.map(|output| output.display().to_string()),
+                position: position + 1,
+                path: path.to_owned(),
+                rotation,
+                header,
+                compressed,
+                isize,
+            });
+        }
+    }
+
+    Ok(listed)
+}
+
+fn format_mtime(mtime: u32) -> String {
+    Utc.timestamp_opt(i64::from(mtime), 0)
+        .single()
+        .map_or_else(|| mtime.to_string(), |time| time.to_rfc3339())
+}
+
+fn write_list_table(out: &mut dyn Write, listed: &[Listed]) -> io::Result<()> {
+    let planned = listed.iter().any(|listed| listed.output.is_some());
+    let unknown = || String::from("-");
+
+    let rows: Vec<Vec<String>> = listed
+        .iter()
+        .map(|listed| {
+            let header = listed.header.as_ref();
+            let mut row = Vec::new();
+            if planned {
+                row.push(listed.output.clone().unwrap_or_else(unknown));
+            }
+            row.extend([
+                listed.position.to_string(),
+                listed.path.clone(),
+                listed.rotation.to_string(),
+                header
+                    .and_then(|header| header.name.clone())
+                    .unwrap_or_else(unknown),
+                header
+                    .and_then(|header| header.mtime)
+                    .map_or_else(unknown, format_mtime),
+                header.map_or_else(unknown, |header| os_name(header.os).to_owned()),
+                listed
+                    .compressed
+                    .map_or_else(unknown, |size| size.to_string()),
+                listed.isize.map_or_else(unknown, |size| size.to_string()),
+            ]);
+            row
+        })
+        .collect();
+
+    let mut header = Vec::new();
+    if planned {
+        header.push(("OUTPUT", Align::Left));
+    }
+    header.extend([
+        ("#", Align::Right),
+        ("FILE", Align::Left),
+        ("ROTATION", Align::Left),
+        ("FNAME", Align::Left),
+        ("MTIME", Align::Left),
+        ("OS", Align::Left),
+        ("COMPRESSED", Align::Right),
+        ("ISIZE", Align::Right),
+    ]);
+    write_table(out, &header, &rows)
+}
+
+fn write_list_json(out: &mut dyn Write, listed: &[Listed]) -> io::Result<()> {
+    let objects: Vec<String> = listed
+        .iter()
+        .map(|listed| {
+            let header = listed.header.as_ref();
+            json::object(&[
+                (
+                    "output",
+                    json::optional(listed.output.as_deref(), json::string),
+                ),
+                ("position", listed.position.to_string()),
+                ("path", json::string(&listed.path)),
+                ("rotation", json::string(&listed.rotation.to_string())),
+                (
+                    "index",
+                    json::optional(listed.rotation.index, |index| index.0.to_string()),
+                ),
+                (
+                    "fname",
+                    json::optional(
+                        header.and_then(|header| header.name.as_deref()),
+                        json::string,
+                    ),
+                ),
+                (
+                    "mtime",
+                    json::optional(header.and_then(|header| header.mtime), |mtime| {
+                        mtime.to_string()
+                    }),
+                ),
+                (
+                    "os",
+                    json::optional(header, |header| json::string(os_name(header.os))),
+                ),
+                (
+                    "compressed",
+                    json::optional(listed.compressed, |size| size.to_string()),
+                ),
+                (
+                    "isize",
+                    json::optional(listed.isize, |size| size.to_string()),
+                ),
+            ])
+        })
+        .collect();
+
+    writeln!(out, "[{}]", objects.join(",\n"))
+}
+
+/// Prints the listed files to standard output.
+pub fn print_list(listed: &[Listed], format: ListFormat) -> io::Result<()> {
+    let mut out = stdout().lock();
+    match format {
+        ListFormat::Table => write_list_table(&mut out, listed),
+        ListFormat::Json => write_list_json(&mut out, listed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::{Compression, GzBuilder};
+    use std::fs::{write, File};
+    use std::io::Write;
+    use std::path::Path;
+
+    use super::{list_files, write_list_json};
+    use anyhow::Result;
+
+    #[test]
+    fn list_files_test() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let rotated = dir.path().join("app.log.1.gz");
+        let mut encoder = GzBuilder::new()
+            .filename("app.log.1")
+            .mtime(1_657_879_201)
+            .write(File::create(&rotated)?, Compression::default());
+        encoder.write_all(b"Hello World\n")?;
+        encoder.finish()?;
+        let live = dir.path().join("app.log");
+        write(&live, b"live\n")?;
+
+        let files = vec![
+            rotated.to_str().unwrap().to_owned(),
+            live.to_str().unwrap().to_owned(),
+        ];
+        let listed = list_files(&[(Some(Path::new("out.log")), &files)], None)?;
+
+        assert_eq!(listed[0].position, 1);
+        assert_eq!(listed[0].rotation.to_string(), "index 1");
+        let header = listed[0].header.as_ref().unwrap();
+        assert_eq!(header.name.as_deref(), Some("app.log.1"));
+        assert_eq!(header.mtime, Some(1_657_879_201));
+        assert_eq!(listed[0].isize, Some(12));
+        assert_eq!(listed[1].header, None);
+        assert_eq!(listed[1].compressed, Some(5));
+
+        let mut json = Vec::new();
+        write_list_json(&mut json, &listed[1..])?;
+        let json = String::from_utf8(json)?;
+        assert!(json.starts_with(r#"[{"output":"out.log","position":2,"#));
+        assert!(json.contains(r#""fname":null"#));
+        Ok(())
+    }
+}
@@ -1,124 +1,771 @@
-use anyhow::{anyhow, Context, Result};
-use clap::Parser;
-use flate2::bufread::GzDecoder;
-use indicatif::{ProgressBar, ProgressStyle};
-use std::collections::BTreeMap;
-use std::fs::{File, OpenOptions};
-use std::io::{copy, BufRead, BufReader, BufWriter, Write};
+mod decompress;
+mod discover;
+mod filter;
+mod index;
+mod input;
+mod json;
+mod lines;
+mod list;
+mod output;
+mod report;
+mod sort;
+mod timestamp;
+mod verify;
 
-#[cfg(test)]
-mod tests {
-    use std::io::{Cursor, Write};
-
-    use super::{decompress_into, sort_files};
-    use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use chrono::{Datelike, Utc};
+use clap::{Args, Parser, Subcommand};
+use decompress::{decompress_into, is_gzip, recover_into, Damage, Encoding, Recovery};
+use discover::{build_globset, discover_inputs, read_files_from, DiscoverOptions};
+use filter::{Filtered, LineFilter, PatternOptions, TimeWindow};
+use index::{write_index, IndexFormat, Slice};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use input::{modified, with_input};
+use lines::{LinePrefix, LineSink, Lines, RecordStart, Records};
+use list::{list_files, print_list, ListFormat};
+use output::{
+    check_outputs, family_outputs, is_stdout, open_output, Output, OutputCompression, OutputOptions,
+};
+use regex::Regex;
+use report::{exit_code, print_summary, FileReport};
+use sort::{group_families, sort_files, MergeOrder, SortBy, SortOptions};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use timestamp::parse_time;
+use verify::{print_verify_summary, verify_file, VerifyReport};
 
-    #[test]
-    fn sort_inputs_test() -> Result<()> {
-        let inputs = vec![
-            String::from("a.log.4.gz"),
-            String::from("a.log.1.gz"),
-            String::from("a.log.30.gz"),
-            String::from("a.log.2.gz"),
-        ];
-        let expected = vec![
-            String::from("a.log.1.gz"),
-            String::from("a.log.2.gz"),
-            String::from("a.log.4.gz"),
-            String::from("a.log.30.gz"),
-        ];
-
-        assert_eq!(expected, sort_files(&inputs)?);
-        Ok(())
-    }
+#[derive(Parser, Debug)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct ProgramArgs {
+    #[clap(subcommand)]
+    command: Option<Command>,
+    // Running without a subcommand merges.
+    #[clap(flatten)]
+    merge: MergeArgs,
+}
 
-    #[test]
-    fn decompress_test() -> Result<()> {
-        let buffer = [
-            0x1f, 0x8b, 0x8, 0x8, 0x60, 0x6d, 0xd8, 0x62, 0x0, 0x3, 0x69, 0x6e, 0x2e, 0x74, 0x78,
-            0x74, 0x0, 0xf3, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0x8, 0xcf, 0x2f, 0xca, 0x49, 0xe1, 0x2,
-            0x0, 0xe3, 0xe5, 0x95, 0xb0, 0xc, 0x0, 0x0, 0x0,
-        ];
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Merge the inputs into one output file, or one per log family.
+    Merge(Box<MergeArgs>),
+    /// Decode every input and check its integrity without writing output.
+    Verify(VerifyArgs),
+    /// Show the merge order with the gzip header fields and sizes of every
+    /// input.
+    List(ListArgs),
+}
 
-        let reader = Cursor::new(&buffer);
-        let mut writer_buf: Vec<u8> = Vec::new();
-        let mut writer = Cursor::new(&mut writer_buf);
+#[derive(Args, Debug)]
+struct MergeArgs {
+    /// Merged output file, `-` for standard output.
+    #[clap(short, long, required_unless_present = "output-dir")]
+    output_file: Option<PathBuf>,
+    /// Write every log family to its own file inside this directory.
+    #[clap(long, conflicts_with = "output-file")]
+    output_dir: Option<PathBuf>,
+    /// Compress the merged output. Under --output-dir the matching extension
+    /// is appended to every file name.
+    #[clap(long, value_enum, value_name = "CODEC")]
+    compress: Option<OutputCompression>,
+    /// Compression level: 0-9 for gzip (default 6), 1-22 for zstd (default
+    /// 3).
+    #[clap(long, requires = "compress")]
+    level: Option<u32>,
+    /// Decompress and re-encode gzip inputs too. By default their members
+    /// are copied into gzip output as they are.
+    #[clap(long, requires = "compress")]
+    recompress: bool,
+    /// Keep the output written so far when the merge fails instead of
+    /// leaving the previous output in place.
+    #[clap(long)]
+    keep_partial: bool,
+    /// Report files that fail to decompress and carry on with the rest,
+    /// keeping what was decoded before the failure. Exits with 2 when only
+    /// some files failed.
+    #[clap(long)]
+    keep_going: bool,
+    /// Keep what decoded before damaged gzip data instead of failing, and
+    /// with `resync` look for the next gzip member or flush point to resume
+    /// from. Damaged files are read into memory and exit with 2.
+    #[clap(long, value_enum, value_name = "MODE")]
+    recover: Option<Recovery>,
+    /// Do not add a newline between two files when the first one does not
+    /// end with it.
+    #[clap(long)]
+    no_newline: bool,
+    /// Put a `==> path <==` line before the content of every file.
+    #[clap(long)]
+    banner: bool,
+    /// Start every line with the path of its file and its line number in
+    /// it, as `path:number:`.
+    #[clap(long)]
+    prefix: bool,
+    /// Treat lines not starting with a timestamp as part of the record
+    /// started by the line before them, so that filters, context and
+    /// prefixes apply to whole records such as stack traces.
+    #[clap(long)]
+    records: bool,
+    /// Like --records, but records start with lines matching this regular
+    /// expression.
+    #[clap(long, value_name = "REGEX")]
+    record_start: Option<regex::bytes::Regex>,
+    /// Write an index next to every output, named after it with
+    /// `.index.json` or `.index.csv` appended, giving for each input file
+    /// where its content starts and ends in the uncompressed output, its
+    /// line count and CRC32.
+    #[clap(long, value_enum, value_name = "FORMAT")]
+    index: Option<IndexFormat>,
+    /// Show what would be merged into which output, as `list` does, instead
+    /// of merging.
+    #[clap(long)]
+    dry_run: bool,
+    /// Format of the --dry-run listing.
+    #[clap(long, value_enum, default_value = "table", requires = "dry-run")]
+    format: ListFormat,
+    #[clap(flatten)]
+    sort: SortArgs,
+    #[clap(flatten)]
+    filter: FilterArgs,
+    #[clap(flatten)]
+    input: InputArgs,
+}
 
-        decompress_into(reader, &mut writer)?;
+// How inputs are put in order. Plain comments here, as clap would take doc
+// comments for the help text of the whole command.
+#[derive(Args, Debug)]
+struct SortArgs {
+    /// Order in which the rotations of a log are merged. Oldest first keeps
+    /// the timestamps of the output going forward.
+    #[clap(long, value_enum, default_value = "oldest-first")]
+    order: MergeOrder,
+    /// Regex matched against file names to order them. The named captures
+    /// `date`, `time`, `seq` (grows with time) and `index` (grows with age,
+    /// like logrotate numbers) build the sort key, compared by their digits,
+    /// and the rest of the name identifies the log.
+    #[clap(long, value_name = "REGEX")]
+    sort_pattern: Option<Regex>,
+    /// Order files by a timestamp instead of their names. The rotation
+    /// scheme of the names breaks ties.
+    #[clap(long, value_enum, default_value = "name")]
+    sort_by: SortBy,
+    /// Group inputs by log name and merge each log on its own instead of
+    /// treating all inputs as rotations of a single log.
+    #[clap(long)]
+    group_families: bool,
+    /// Fail when logrotate numbers or dates are missing from a rotation
+    /// instead of warning about it.
+    #[clap(long)]
+    strict: bool,
+}
 
-        writer.flush()?;
-        let got = String::from_utf8(writer_buf)?;
-        let expected = String::from("Hello World\n");
+#[derive(Args, Debug)]
+struct FilterArgs {
+    /// Only keep lines matching this regular expression.
+    #[clap(long = "match", value_name = "REGEX")]
+    matches: Vec<String>,
+    /// Drop lines matching this regular expression.
+    #[clap(long, value_name = "REGEX")]
+    exclude_match: Vec<String>,
+    /// Match the patterns regardless of case.
+    #[clap(short, long)]
+    ignore_case: bool,
+    /// Take the patterns as plain strings instead of regular expressions.
+    #[clap(short = 'F', long)]
+    fixed_strings: bool,
+    /// Also keep this many lines after every matching line.
+    #[clap(short = 'A', long, value_name = "NUM")]
+    after_context: Option<usize>,
+    /// Also keep this many lines before every matching line.
+    #[clap(short = 'B', long, value_name = "NUM")]
+    before_context: Option<usize>,
+    /// Also keep this many lines before and after every matching line.
+    #[clap(short = 'C', long, value_name = "NUM")]
+    context: Option<usize>,
+    /// Only keep lines from this time on, given as a timestamp like
+    /// 2022-07-15T10:00 or as a duration ago like 2h. Timestamps without a
+    /// zone, here and in the logs, are taken as UTC.
+    #[clap(long, value_name = "TIME")]
+    since: Option<String>,
+    /// Only keep lines up to this time, given like --since.
+    #[clap(long, value_name = "TIME")]
+    until: Option<String>,
+}
 
-        assert_eq!(got, expected);
+impl SortArgs {
+    fn options(&self) -> SortOptions {
+        SortOptions {
+            order: self.order,
+            pattern: self.sort_pattern.clone(),
+            sort_by: self.sort_by,
+            strict: self.strict,
+        }
+    }
 
-        Ok(())
+    /// Puts `inputs` in merge order, log after log with --group-families.
+    fn merge_order(&self, inputs: &[String]) -> Result<Vec<String>> {
+        let options = self.options();
+        if self.group_families {
+            Ok(group_families(inputs, &options)?
+                .into_iter()
+                .flat_map(|family| family.files)
+                .collect())
+        } else {
+            sort_files(inputs, &options)
+        }
     }
 }
 
-#[derive(Parser, Debug)]
-struct ProgramArgs {
-    #[clap(short, long)]
-    output_file: String,
+// Which files are read.
+#[derive(Args, Debug)]
+struct InputArgs {
+    /// Only pick up files matching this glob while scanning directories.
+    #[clap(long, value_name = "GLOB")]
+    include: Vec<String>,
+    /// Skip files matching this glob while scanning directories.
+    #[clap(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+    /// Do not descend more than this many levels into directory inputs.
+    #[clap(long)]
+    max_depth: Option<usize>,
+    /// Follow symbolic links while scanning directories.
+    #[clap(long)]
+    follow_symlinks: bool,
+    /// Read additional input paths from this file, one per line.
+    #[clap(long, value_name = "FILE")]
+    files_from: Option<PathBuf>,
+    /// Entries of --files-from are separated by NUL instead of newline.
+    #[clap(short = '0', long, requires = "files-from")]
+    null: bool,
+    /// Input files or directories to scan recursively, `-` for standard
+    /// input.
     input_files: Vec<String>,
 }
 
-fn decompress_into<R: BufRead, W: Write>(reader: R, writer: &mut W) -> Result<()> {
-    let mut decoder = GzDecoder::new(reader);
-    copy(&mut decoder, writer)?;
+impl FilterArgs {
+    /// Builds the line filter, `None` when every line is kept.
+    fn filter(&self) -> Result<Option<LineFilter>> {
+        let now = Utc::now().timestamp_millis();
+        let parse = |time: &Option<String>| time.as_deref().map(|time| parse_time(time, now));
+        let window = TimeWindow {
+            since: parse(&self.since).transpose()?,
+            until: parse(&self.until).transpose()?,
+        };
 
-    Ok(())
+        let context = self.context.unwrap_or(0);
+        LineFilter::new(
+            &self.matches,
+            &self.exclude_match,
+            PatternOptions {
+                ignore_case: self.ignore_case,
+                fixed_strings: self.fixed_strings,
+            },
+            window,
+            self.before_context.unwrap_or(context),
+            self.after_context.unwrap_or(context),
+        )
+    }
 }
 
-fn sort_files(files: &Vec<String>) -> Result<Vec<String>> {
-    let result: Result<BTreeMap<u32, String>> = files
-        .iter()
-        .map(|item| {
-            let number = item
-                .rsplit(".")
-                .skip(1)
-                .next()
-                .ok_or(anyhow!("Wrong filename format! ({})", item))?
-                .parse::<u32>()?;
-
-            Ok((number, item.to_owned()))
-        })
-        .collect();
-
-    Ok(result?.into_iter().map(|(_, value)| value).collect())
+impl InputArgs {
+    /// Collects the input files from the arguments, the --files-from list
+    /// and the directories among them.
+    fn discover(&self) -> Result<Vec<String>> {
+        let mut inputs = self.input_files.clone();
+        if let Some(files_from) = &self.files_from {
+            inputs.extend(read_files_from(files_from, self.null)?);
+        }
+        let options = DiscoverOptions {
+            include: build_globset(&self.include)?,
+            exclude: build_globset(&self.exclude)?,
+            max_depth: self.max_depth,
+            follow_symlinks: self.follow_symlinks,
+        };
+        discover_inputs(&inputs, &options)
+    }
 }
 
-fn main() -> Result<()> {
-    let args = ProgramArgs::parse();
-    let sorted = sort_files(&args.input_files)?;
-
-    let output_file = OpenOptions::new()
-        .truncate(true)
-        .write(true)
-        .create(true)
-        .open(args.output_file)?;
+#[derive(Args, Debug)]
+struct ListArgs {
+    #[clap(long, value_enum, default_value = "table")]
+    format: ListFormat,
+    #[clap(flatten)]
+    sort: SortArgs,
+    #[clap(flatten)]
+    input: InputArgs,
+}
 
-    let mut writer = BufWriter::new(output_file);
+#[derive(Args, Debug)]
+struct VerifyArgs {
+    #[clap(flatten)]
+    input: InputArgs,
+}
 
-    let bar = ProgressBar::new(sorted.len() as u64);
+/// Progress bar over `total` files, hidden when writing to standard output
+/// as it would garble a terminal or pager reading it.
+fn progress_bar(total: usize, to_stdout: bool) -> ProgressBar {
+    let bar = ProgressBar::new(total as u64);
     bar.set_style(
         ProgressStyle::default_bar()
             .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}")
             .progress_chars("##-"),
     );
+    if to_stdout {
+        bar.set_draw_target(ProgressDrawTarget::hidden());
+    }
+    bar
+}
+
+/// How input files are merged into an output.
+#[derive(Debug, Clone, Copy)]
+struct MergeOptions<'a> {
+    /// Decode gzip inputs even when they could be copied into gzip output.
+    recompress: bool,
+    /// Report failing files and carry on instead of stopping.
+    keep_going: bool,
+    recover: Option<Recovery>,
+    /// Start every file on a new line.
+    line_boundaries: bool,
+    banner: bool,
+    prefix: bool,
+    filter: Option<&'a LineFilter>,
+    records: Option<&'a RecordStart>,
+}
 
-    for filepath in &sorted {
+impl MergeOptions<'_> {
+    /// Returns true when the content has to be decoded, as opposed to
+    /// copying gzip members into gzip output. A copied member is only
+    /// checked once it is in the output, too late to carry on past it.
+    fn decodes(&self) -> bool {
+        self.recompress || self.keep_going || self.recover.is_some() || self.splits_lines()
+    }
+
+    /// Returns true when the content goes through the output line by line.
+    fn splits_lines(&self) -> bool {
+        self.prefix || self.filter.is_some() || self.records.is_some()
+    }
+}
+
+/// Decodes the content of `reader` into `writer`.
+fn decode_file(
+    reader: &mut dyn BufRead,
+    path: &str,
+    mut writer: &mut dyn Write,
+    recover: Option<Recovery>,
+) -> Result<(Encoding, Vec<Damage>)> {
+    match recover {
+        Some(recovery) => recover_into(reader, path, &mut writer, recovery),
+        None => Ok((decompress_into(reader, path, &mut writer)?, Vec::new())),
+    }
+}
+
+/// Decodes the content of `reader` into `writer` line by line, or record by
+/// record, dropping those the filter does not select and prefixing the
+/// others.
+fn decode_lines(
+    reader: &mut dyn BufRead,
+    path: &str,
+    writer: &mut Output,
+    options: MergeOptions,
+) -> Result<(Encoding, Vec<Damage>)> {
+    let sink: Box<dyn LineSink + '_> = if options.prefix {
+        Box::new(LinePrefix::new(writer, path))
+    } else {
+        Box::new(writer)
+    };
+    let sink: Box<dyn LineSink + '_> = match options.filter {
+        Some(filter) => {
+            // Syslog timestamps have no year, take the one the file was
+            // last written in.
+            let year = if filter.window().is_bounded() {
+                modified(path)?.year()
+            } else {
+                Utc::now().year()
+            };
+            Box::new(Filtered::new(filter, sink, year))
+        }
+        None => sink,
+    };
+
+    let sink: Box<dyn LineSink + '_> = match options.records {
+        Some(start) => Box::new(Records::new(start, sink)),
+        None => sink,
+    };
+
+    let mut lines = Lines::new(sink);
+    let result = decode_file(reader, path, &mut lines, options.recover);
+    // Whatever was decoded before a failure still goes out.
+    lines.finish()?;
+    result
+}
+
+/// Decompresses `files` one after another into `writer`. Gzip inputs are
+/// copied verbatim into gzip output unless they have to be decoded.
+fn merge_files(
+    files: &[String],
+    writer: &mut Output,
+    options: MergeOptions,
+    bar: &ProgressBar,
+) -> Result<Vec<FileReport>> {
+    let mut reports = Vec::with_capacity(files.len());
+    for filepath in files {
         bar.set_message(format!("Process {}", &filepath));
         bar.inc(1);
 
-        let file = File::open(filepath)
-            .with_context(|| format!("Failed to open archive file ({})", filepath))?;
-        let reader = BufReader::new(file);
+        if options.line_boundaries {
+            writer.end_line()?;
+        }
+        writer.reset_crc();
+        let mut written = writer.written();
+        let (mut start, mut newlines) = (writer.content().len, writer.content().newlines);
+        let result = with_input(filepath, |reader| {
+            // The banner only goes out once the file could be opened, and is
+            // neither part of its bytes nor of its slice.
+            if options.banner {
+                // A blank line sets the banner apart from the previous file.
+                if writer.written() > 0 {
+                    writer.write_all(b"\n")?;
+                }
+                writeln!(writer, "==> {} <==", filepath)?;
+                writer.reset_crc();
+                written = writer.written();
+                (start, newlines) = (writer.content().len, writer.content().newlines);
+            }
+
+            if !options.decodes() && writer.accepts_gzip() && is_gzip(reader.fill_buf()?) {
+                Ok((writer.copy_gzip(reader)?, Vec::new()))
+            } else if options.splits_lines() {
+                decode_lines(reader, filepath, writer, options)
+            } else {
+                decode_file(reader, filepath, writer, options.recover)
+            }
+        })
+        .with_context(|| format!("Failed to decompress archive file ({})", filepath));
+
+        let (result, damage) = match result {
+            Ok((encoding, damage)) => (Ok(encoding), damage),
+            // A failing output, like a full disk, is no input's fault.
+            Err(error) if options.keep_going && !writer.failed() => {
+                bar.println(format!("Error: {:#}", error));
+                // The first context only repeats the path.
+                let causes: Vec<String> = error.chain().skip(1).map(|e| e.to_string()).collect();
+                (Err(causes.join(": ")), Vec::new())
+            }
+            Err(error) => return Err(error),
+        };
+        for damage in &damage {
+            bar.println(format!("Warning: {}: {}", filepath, damage));
+        }
+        let content = writer.content();
+        // A last line without newline still counts.
+        let unterminated = content.len > start && content.in_line();
+        let slice = Slice {
+            start,
+            end: content.len,
+            lines: content.newlines - newlines + u64::from(unterminated),
+            crc32: content.crc32(),
+        };
+        reports.push(FileReport {
+            path: filepath.to_owned(),
+            bytes: writer.written() - written,
+            result,
+            damage,
+            slice,
+        });
+    }
+
+    Ok(reports)
+}
+
+/// Drops the inputs that have no line in `window` from `jobs`.
+fn skip_outside_window(
+    jobs: Vec<(PathBuf, Vec<String>)>,
+    window: TimeWindow,
+) -> Vec<(PathBuf, Vec<String>)> {
+    let mut kept = Vec::with_capacity(jobs.len());
+    for (output, files) in jobs {
+        let mut inside = Vec::with_capacity(files.len());
+        for file in files {
+            if window.may_contain_file(&file) {
+                inside.push(file);
+            } else {
+                eprintln!("Skipping {}: all of it is outside --since/--until", file);
+            }
+        }
+        kept.push((output, inside));
+    }
+    kept
+}
+
+fn merge(args: MergeArgs) -> Result<ExitCode> {
+    let inputs = args.input.discover()?;
+    let output_options = OutputOptions {
+        compression: args.compress,
+        level: args.level,
+        keep_partial: args.keep_partial,
+    };
+    output_options.validate()?;
+    let filter = args.filter.filter()?;
+    let records = match &args.record_start {
+        Some(pattern) => Some(RecordStart::Pattern(pattern.clone())),
+        None if args.records => Some(RecordStart::Timestamp),
+        None => None,
+    };
+    let sort_options = args.sort.options();
+
+    // Every job is one output file together with the inputs merged into it.
+    let jobs: Vec<(PathBuf, Vec<String>)> = if let Some(output_dir) = &args.output_dir {
+        let families = group_families(&inputs, &sort_options)?;
+        let mut outputs = family_outputs(&families, output_dir)?;
+        if let Some(compression) = args.compress {
+            for output in &mut outputs {
+                output.as_mut_os_string().push(compression.extension());
+            }
+        }
+        outputs
+            .into_iter()
+            .zip(families.into_iter().map(|family| family.files))
+            .collect()
+    } else {
+        let sorted = args.sort.merge_order(&inputs)?;
+        let output_file = args
+            .output_file
+            .clone()
+            .expect("clap requires --output-file without --output-dir");
+        vec![(output_file, sorted)]
+    };
+    let jobs = match &filter {
+        Some(filter) => skip_outside_window(jobs, filter.window()),
+        None => jobs,
+    };
+
+    let output_paths: Vec<&Path> = jobs.iter().map(|(path, _)| path.as_path()).collect();
+    check_outputs(&output_paths, &inputs)?;
+    if args.index.is_some() && output_paths.iter().any(|path| is_stdout(path)) {
+        bail!("--index needs an output file, not standard output");
+    }
+
+    if args.dry_run {
+        let planned: Vec<(Option<&Path>, &[String])> = jobs
+            .iter()
+            .map(|(output, files)| (Some(output.as_path()), files.as_slice()))
+            .collect();
+        let listed = list_files(&planned, args.sort.sort_pattern.as_ref())?;
+        print_list(&listed, args.format)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let merge_options = MergeOptions {
+        recompress: args.recompress,
+        keep_going: args.keep_going,
+        recover: args.recover,
+        line_boundaries: !args.no_newline,
+        banner: args.banner,
+        prefix: args.prefix,
+        filter: filter.as_ref(),
+        records: records.as_ref(),
+    };
+
+    let total: usize = jobs.iter().map(|(_, files)| files.len()).sum();
+    let to_stdout = jobs.iter().any(|(output_path, _)| is_stdout(output_path));
+    let bar = progress_bar(total, to_stdout);
 
-        decompress_into(reader, &mut writer)?;
+    let mut reports = Vec::with_capacity(total);
+    for (output_path, files) in &jobs {
+        let mut writer = open_output(output_path, output_options)?;
+        let job_reports = merge_files(files, &mut writer, merge_options, &bar)?;
+
+        // An output none of whose inputs could be read is left as it was.
+        if job_reports.is_empty() || job_reports.iter().any(|report| report.result.is_ok()) {
+            writer.finish()?;
+            if let Some(format) = args.index {
+                write_index(output_path, format, &job_reports)?;
+            }
+        }
+        reports.extend(job_reports);
     }
 
     bar.finish();
+    print_summary(&reports)?;
+
+    Ok(exit_code(&reports))
+}
+
+fn verify(args: VerifyArgs) -> Result<ExitCode> {
+    let inputs = args.input.discover()?;
+
+    let bar = progress_bar(inputs.len(), false);
+    let reports: Vec<VerifyReport> = inputs
+        .iter()
+        .map(|path| {
+            bar.set_message(format!("Verify {}", path));
+            bar.inc(1);
+            verify_file(path)
+        })
+        .collect();
+    bar.finish();
+
+    print_verify_summary(&reports)?;
+    if reports.iter().all(|report| report.result.is_ok()) {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        Ok(ExitCode::FAILURE)
+    }
+}
 
-    Ok(())
+fn list(args: ListArgs) -> Result<ExitCode> {
+    let inputs = args.input.discover()?;
+    let sorted = args.sort.merge_order(&inputs)?;
+
+    let listed = list_files(&[(None, &sorted)], args.sort.sort_pattern.as_ref())?;
+    print_list(&listed, args.format)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> Result<ExitCode> {
+    let args = ProgramArgs::parse();
+
+    match args.command {
+        Some(Command::Merge(merge_args)) => merge(*merge_args),
+        Some(Command::Verify(verify_args)) => verify(verify_args),
+        Some(Command::List(list_args)) => list(list_args),
+        None => merge(args.merge),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::read::MultiGzDecoder;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use indicatif::ProgressBar;
+    use std::fs::{read_to_string, write, File};
+    use std::io::{Read, Write};
+    use tempfile::tempdir;
+
+    use super::{merge_files, progress_bar, MergeOptions};
+    use crate::output::{open_output, OutputCompression, OutputOptions};
+    use anyhow::Result;
+
+    fn options() -> MergeOptions<'static> {
+        MergeOptions {
+            recompress: false,
+            keep_going: false,
+            recover: None,
+            line_boundaries: true,
+            banner: false,
+            prefix: false,
+            filter: None,
+            records: None,
+        }
+    }
+
+    fn gzip(content: &str) -> Result<Vec<u8>> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(content.as_bytes())?;
+        Ok(encoder.finish()?)
+    }
+
+    #[test]
+    fn banner_test() -> Result<()> {
+        let dir = tempdir()?;
+        let mut files = Vec::new();
+        for (name, content) in [("app.log.1", "one\ntwo"), ("app.log", "live\n")] {
+            let path = dir.path().join(name);
+            write(&path, content)?;
+            files.push(path.to_string_lossy().into_owned());
+        }
+
+        let output = dir.path().join("out.log");
+        let mut writer = open_output(&output, OutputOptions::default())?;
+        let options = MergeOptions {
+            banner: true,
+            ..options()
+        };
+        let reports = merge_files(&files, &mut writer, options, &ProgressBar::hidden())?;
+        writer.finish()?;
+
+        // The unterminated line is ended before the blank line and banner.
+        let first = format!("==> {} <==\n", files[0]);
+        let second = format!("==> {} <==\n", files[1]);
+        let merged = read_to_string(&output)?;
+        assert_eq!(merged, format!("{}one\ntwo\n\n{}live\n", first, second));
+
+        // The slices only cover the content of the files.
+        let slices: Vec<&str> = reports
+            .iter()
+            .map(|report| &merged[report.slice.start as usize..report.slice.end as usize])
+            .collect();
+        assert_eq!(slices, ["one\ntwo", "live\n"]);
+        assert_eq!(reports[0].slice.lines, 2);
+
+        // A file that cannot be opened gets no banner and no bytes.
+        let missing = dir.path().join("app.log.2").to_string_lossy().into_owned();
+        let files = [missing, files[1].clone()];
+        let output = dir.path().join("out.log");
+        let mut writer = open_output(&output, OutputOptions::default())?;
+        let options = MergeOptions {
+            keep_going: true,
+            ..options
+        };
+        let reports = merge_files(&files, &mut writer, options, &ProgressBar::hidden())?;
+        writer.finish()?;
+        assert_eq!(read_to_string(&output)?, format!("{}live\n", second));
+        assert!(reports[0].result.is_err());
+        assert_eq!(reports[0].bytes, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn progress_bar_test() {
+        assert!(progress_bar(3, true).is_hidden());
+    }
+
+    #[test]
+    fn keep_going_gzip_test() -> Result<()> {
+        let dir = tempdir()?;
+        let mut files = Vec::new();
+        for (name, content) in [
+            ("app.log.3.gz", "three\n"),
+            ("app.log.2.gz", "two\n"),
+            ("app.log.1.gz", "one\n"),
+        ] {
+            let mut data = gzip(content)?;
+            if name == "app.log.2.gz" {
+                // Break the CRC32 of the member.
+                let crc = data.len() - 8;
+                data[crc] ^= 0xff;
+            }
+            let path = dir.path().join(name);
+            write(&path, data)?;
+            files.push(path.to_string_lossy().into_owned());
+        }
+
+        let output = dir.path().join("out.log.gz");
+        let mut writer = open_output(
+            &output,
+            OutputOptions {
+                compression: Some(OutputCompression::Gzip),
+                ..OutputOptions::default()
+            },
+        )?;
+        let options = MergeOptions {
+            keep_going: true,
+            ..options()
+        };
+        let reports = merge_files(&files, &mut writer, options, &ProgressBar::hidden())?;
+        writer.finish()?;
+
+        let failed: Vec<bool> = reports
+            .iter()
+            .map(|report| report.result.is_err())
+            .collect();
+        assert_eq!(failed, [false, true, false]);
+
+        // What the damaged file held is salvaged and the output stays valid.
+        let mut merged = String::new();
+        MultiGzDecoder::new(File::open(&output)?).read_to_string(&mut merged)?;
+        assert_eq!(merged, "three\ntwo\none\n");
+        Ok(())
+    }
 }
@@ -0,0 +1,864 @@
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use flate2::write::GzEncoder;
+use flate2::{Compression, Crc};
+use std::collections::HashSet;
+use std::ffi::OsString;
+#[cfg(unix)]
+use std::fs::metadata;
+use std::fs::{canonicalize, create_dir_all, remove_file, File, OpenOptions, TryLockError};
+use std::io::{self, stdout, BufReader, BufWriter, Read, Write};
+use std::ops::RangeInclusive;
+use std::path::{Component, Path, PathBuf};
+use tempfile::{Builder, TempPath};
+
+use crate::decompress::{decompress_gzip_into, Encoding};
+use crate::input::{split_entry_path, STDIO_PATH};
+use crate::sort::Family;
+
+/// Family name as a path, with the entries of a bundle placed in a
+/// directory named after the bundle.
+fn family_path(name: &str) -> PathBuf {
+    match split_entry_path(name) {
+        Some((archive, entry)) => Path::new(archive).join(entry),
+        None => PathBuf::from(name),
+    }
+}
+
+/// Directory part of a family path, ignoring `.` components.
+fn parent_components(family: &Path) -> Vec<Component<'_>> {
+    family
+        .parent()
+        .map(|parent| {
+            parent
+                .components()
+                .filter(|component| *component != Component::CurDir)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Maps every family to its own output file under `dir`. The directory
+/// layout shared by all families is stripped, so `/var/log/syslog` and
+/// `/var/log/nginx/access.log` become `dir/syslog` and `dir/nginx/access.log`.
+pub fn family_outputs(families: &[Family], dir: &Path) -> Result<Vec<PathBuf>> {
+    let paths: Vec<PathBuf> = families
+        .iter()
+        .map(|family| family_path(&family.name))
+        .collect();
+
+    let mut common: Option<Vec<Component>> = None;
+    for path in &paths {
+        let parent = parent_components(path);
+        common = Some(match common {
+            None => parent,
+            Some(common) => common
+                .into_iter()
+                .zip(parent)
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect(),
+        });
+    }
+    let common_len = common.map_or(0, |common| common.len());
+
+    let mut seen = HashSet::new();
+    paths
+        .iter()
+        .map(|path| {
+            let relative: PathBuf = path
+                .components()
+                .filter(|component| *component != Component::CurDir)
+                .skip(common_len)
+                .filter(|component| matches!(component, Component::Normal(_)))
+                .collect();
+            let output = dir.join(relative);
+
+            if !seen.insert(output.clone()) {
+                bail!(
+                    "Several logs map to the same output file ({})",
+                    output.display()
+                );
+            }
+            Ok(output)
+        })
+        .collect()
+}
+
+/// Compression applied to the merged output.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputCompression {
+    Gzip,
+    Zstd,
+}
+
+impl OutputCompression {
+    /// Extension appended to the names of per-family outputs.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputCompression::Gzip => ".gz",
+            OutputCompression::Zstd => ".zst",
+        }
+    }
+
+    fn levels(self) -> RangeInclusive<u32> {
+        match self {
+            OutputCompression::Gzip => 0..=9,
+            OutputCompression::Zstd => 1..=22,
+        }
+    }
+
+    fn default_level(self) -> u32 {
+        match self {
+            OutputCompression::Gzip => 6,
+            OutputCompression::Zstd => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OutputOptions {
+    pub compression: Option<OutputCompression>,
+    /// Compression level, the codec's default when `None`.
+    pub level: Option<u32>,
+    /// Move an unfinished output into place instead of deleting it.
+    pub keep_partial: bool,
+}
+
+impl OutputOptions {
+    /// Checks the level against the chosen codec.
+    pub fn validate(&self) -> Result<()> {
+        match (self.compression, self.level) {
+            (Some(compression), Some(level)) if !compression.levels().contains(&level) => {
+                bail!(
+                    "Compression level {} is out of range for {:?} ({:?})",
+                    level,
+                    compression,
+                    compression.levels()
+                )
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+enum State {
+    /// Nothing is being compressed right now.
+    Idle(Box<dyn Write>),
+    Gzip(GzEncoder<Box<dyn Write>>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, Box<dyn Write>>),
+}
+
+/// File being written next to its final path, renamed over it once the
+/// output is complete.
+struct Pending {
+    /// Handle for syncing, shared with the buffered writer.
+    file: File,
+    /// Advisory lock on the output path, released on drop.
+    _lock: File,
+    /// Lock file, removed on drop while still locked.
+    lock_path: PathBuf,
+    temp: Option<TempPath>,
+    path: PathBuf,
+    keep_partial: bool,
+}
+
+impl Pending {
+    fn commit(&mut self) -> Result<()> {
+        self.file
+            .sync_all()
+            .with_context(|| format!("Failed to sync output file ({})", self.path.display()))?;
+
+        let temp = self.temp.take().expect("output is committed once");
+        temp.persist(&self.path)
+            .with_context(|| format!("Failed to rename output file ({})", self.path.display()))?;
+
+        // The rename itself is only durable once the directory is synced.
+        if let Some(parent) = self.path.parent() {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            File::open(parent)
+                .and_then(|dir| dir.sync_all())
+                .with_context(|| {
+                    format!("Failed to sync output directory ({})", parent.display())
+                })?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Pending {
+    /// When the merge failed, the temporary file is deleted unless the
+    /// partial output was asked for.
+    fn drop(&mut self) {
+        if let Some(temp) = self.temp.take() {
+            if self.keep_partial {
+                if let Err(error) = temp.persist(&self.path) {
+                    eprintln!(
+                        "Failed to keep partial output ({}): {}",
+                        self.path.display(),
+                        error
+                    );
+                }
+            }
+        }
+        // Not to leave one behind per output. A run waiting on it notices
+        // it is gone once it gets the lock and starts over.
+        let _ = remove_file(&self.lock_path);
+    }
+}
+
+/// Reader that copies everything read through it into another writer.
+struct Tee<'a, R: ?Sized> {
+    inner: &'a mut R,
+    copy: &'a mut Box<dyn Write>,
+    copied: u64,
+    /// Writing the copy failed, as opposed to reading.
+    failed: bool,
+}
+
+impl<R: Read + ?Sized> Read for Tee<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if let Err(error) = self.copy.write_all(&buf[..read]) {
+            self.failed = true;
+            return Err(error);
+        }
+        self.copied += read as u64;
+        Ok(read)
+    }
+}
+
+/// Running totals of the uncompressed content of an output.
+#[derive(Debug, Default)]
+pub struct Content {
+    /// Bytes so far, the offset of whatever comes next.
+    pub len: u64,
+    /// Newlines so far.
+    pub newlines: u64,
+    /// CRC32 of the bytes since the last [`Output::reset_crc`].
+    crc: Crc,
+    last_byte: Option<u8>,
+}
+
+impl Content {
+    fn update(&mut self, buf: &[u8]) {
+        self.len += buf.len() as u64;
+        self.newlines += buf.iter().filter(|&&byte| byte == b'\n').count() as u64;
+        self.crc.update(buf);
+        if let Some(&last) = buf.last() {
+            self.last_byte = Some(last);
+        }
+    }
+
+    pub fn crc32(&self) -> u32 {
+        self.crc.sum()
+    }
+
+    /// Returns true when the content ends in the middle of a line.
+    pub fn in_line(&self) -> bool {
+        self.last_byte.is_some_and(|byte| byte != b'\n')
+    }
+}
+
+impl Write for Content {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writer of a merged output that compresses everything written to it. With
+/// gzip, existing gzip members can also be copied in verbatim; the encoder
+/// then ends its member first and starts a new one on the next write.
+pub struct Output {
+    options: OutputOptions,
+    /// Always `Some` outside of state transitions.
+    state: Option<State>,
+    /// Bytes accepted so far, compressed ones for copied gzip members.
+    written: u64,
+    content: Content,
+    /// Writing failed, so the output cannot be trusted whatever the inputs.
+    failed: bool,
+    /// `None` for standard output. Declared after `state` so that the buffer
+    /// is flushed before a partial file is kept.
+    pending: Option<Pending>,
+}
+
+impl Output {
+    fn state(&mut self) -> &mut State {
+        self.state.as_mut().expect("output state is always set")
+    }
+
+    /// Makes sure an encoder is running when compression is on.
+    fn start(&mut self) -> io::Result<()> {
+        let state = self.state.take().expect("output state is always set");
+        let level = self.options.level;
+
+        self.state = Some(match (state, self.options.compression) {
+            (State::Idle(sink), Some(OutputCompression::Gzip)) => {
+                let level = level.unwrap_or_else(|| OutputCompression::Gzip.default_level());
+                State::Gzip(GzEncoder::new(sink, Compression::new(level)))
+            }
+            #[cfg(feature = "zstd")]
+            (State::Idle(sink), Some(OutputCompression::Zstd)) => {
+                let level = level.unwrap_or_else(|| OutputCompression::Zstd.default_level());
+                State::Zstd(zstd::Encoder::new(sink, level as i32)?)
+            }
+            (state, _) => state,
+        });
+
+        Ok(())
+    }
+
+    /// Ends the running encoder, if any, and returns to writing raw bytes.
+    fn stop(&mut self) -> io::Result<()> {
+        let state = self.state.take().expect("output state is always set");
+        self.state = Some(State::Idle(match state {
+            State::Idle(sink) => sink,
+            State::Gzip(encoder) => encoder.finish()?,
+            #[cfg(feature = "zstd")]
+            State::Zstd(encoder) => encoder.finish()?,
+        }));
+
+        Ok(())
+    }
+
+    /// Returns true when gzip input can be copied in without compressing it
+    /// again.
+    pub fn accepts_gzip(&self) -> bool {
+        self.options.compression == Some(OutputCompression::Gzip)
+    }
+
+    /// Copies the gzip members read from `reader` into the output as they
+    /// are. Only valid when [`Output::accepts_gzip`] holds. The members are
+    /// still decoded on the side, which checks and counts them and tells how
+    /// their content ends.
+    pub fn copy_gzip<R: Read + ?Sized>(&mut self, reader: &mut R) -> Result<Encoding> {
+        debug_assert!(self.accepts_gzip());
+        if let Err(error) = self.stop() {
+            self.failed = true;
+            return Err(error.into());
+        }
+        let State::Idle(sink) = self.state.as_mut().expect("output state is always set") else {
+            unreachable!("stop() leaves the output idle");
+        };
+
+        let mut tee = Tee {
+            inner: reader,
+            copy: sink,
+            copied: 0,
+            failed: false,
+        };
+        let result = decompress_gzip_into(BufReader::new(&mut tee), &mut self.content);
+        self.written += tee.copied;
+        self.failed |= tee.failed;
+        result
+    }
+
+    /// Writes a newline unless the content written so far is empty or
+    /// already ends with one.
+    pub fn end_line(&mut self) -> io::Result<()> {
+        if self.content.in_line() {
+            self.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    /// Returns true when writing the output failed, as opposed to reading
+    /// an input.
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    pub fn content(&self) -> &Content {
+        &self.content
+    }
+
+    /// Starts the CRC32 of [`Content`] over.
+    pub fn reset_crc(&mut self) {
+        self.content.crc.reset();
+    }
+
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Ends the compressed stream, flushes everything to the sink and moves
+    /// the output file into place. Dropping the output without finishing it
+    /// leaves the previous content of the path alone.
+    pub fn finish(mut self) -> Result<()> {
+        self.stop()?;
+        self.flush()?;
+        if let Some(pending) = &mut self.pending {
+            pending.commit()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Output {
+    /// Completes the compressed stream of an output kept after a failure, so
+    /// that it can be decompressed up to the failure.
+    fn drop(&mut self) {
+        let pending = self.pending.as_ref();
+        let keep_partial =
+            pending.is_some_and(|pending| pending.temp.is_some() && pending.keep_partial);
+        if keep_partial {
+            let _ = self.stop();
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Err(error) = self.start() {
+            self.failed = true;
+            return Err(error);
+        }
+        let written = match self.state() {
+            State::Idle(sink) => sink.write(buf),
+            State::Gzip(encoder) => encoder.write(buf),
+            #[cfg(feature = "zstd")]
+            State::Zstd(encoder) => encoder.write(buf),
+        };
+        let written = written.inspect_err(|_| self.failed = true)?;
+        self.written += written as u64;
+        self.content.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let result = match self.state() {
+            State::Idle(sink) => sink.flush(),
+            State::Gzip(encoder) => encoder.flush(),
+            #[cfg(feature = "zstd")]
+            State::Zstd(encoder) => encoder.flush(),
+        };
+        result.inspect_err(|_| self.failed = true)
+    }
+}
+
+/// Returns true when `path` stands for standard output.
+pub fn is_stdout(path: &Path) -> bool {
+    path == Path::new(STDIO_PATH)
+}
+
+/// Name of a hidden file next to `path`, like `.app.log.lock`.
+fn hidden_name(path: &Path, suffix: &str) -> OsString {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(suffix);
+    name
+}
+
+/// Takes an advisory lock on a lock file next to `path`, so that
+/// concurrent runs do not write the same output, and returns it with its
+/// path. The output itself cannot be locked as it gets replaced by a rename.
+fn lock_output(path: &Path, parent: &Path) -> Result<(File, PathBuf)> {
+    let lock_path = parent.join(hidden_name(path, ".lock"));
+    loop {
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .with_context(|| format!("Failed to open lock file ({})", lock_path.display()))?;
+
+        match lock.try_lock() {
+            Ok(()) if is_linked(&lock, &lock_path) => return Ok((lock, lock_path)),
+            // The run holding the lock removed the file in the meantime.
+            Ok(()) => continue,
+            Err(TryLockError::WouldBlock) => bail!(
+                "Another run is writing {} (locked through {})",
+                path.display(),
+                lock_path.display()
+            ),
+            Err(TryLockError::Error(error)) => {
+                return Err(error)
+                    .with_context(|| format!("Failed to lock output file ({})", path.display()))
+            }
+        }
+    }
+}
+
+/// Returns true when `file` is still the one found at `path`.
+#[cfg(unix)]
+fn is_linked(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (file.metadata(), metadata(path)) {
+        (Ok(file), Ok(path)) => (file.dev(), file.ino()) == (path.dev(), path.ino()),
+        _ => false,
+    }
+}
+
+/// An open file cannot be removed elsewhere.
+#[cfg(not(unix))]
+fn is_linked(_file: &File, _path: &Path) -> bool {
+    true
+}
+
+/// Identity of an existing file: its canonical path and, on Unix, its device
+/// and inode, which also catch hard links.
+#[derive(PartialEq, Eq)]
+struct FileId {
+    path: PathBuf,
+    #[cfg(unix)]
+    inode: (u64, u64),
+}
+
+impl FileId {
+    fn of(path: &Path) -> Option<FileId> {
+        Some(FileId {
+            path: canonicalize(path).ok()?,
+            #[cfg(unix)]
+            inode: {
+                use std::os::unix::fs::MetadataExt;
+                let metadata = metadata(path).ok()?;
+                (metadata.dev(), metadata.ino())
+            },
+        })
+    }
+
+    fn same_file(&self, other: &FileId) -> bool {
+        #[cfg(unix)]
+        if self.inode == other.inode {
+            return true;
+        }
+        self.path == other.path
+    }
+}
+
+/// Fails when an output would replace one of the inputs, which would be
+/// lost before being read. Entries of a bundle count as the bundle file.
+pub fn check_outputs(outputs: &[&Path], inputs: &[String]) -> Result<()> {
+    let outputs: Vec<(&Path, FileId)> = outputs
+        .iter()
+        .filter(|output| !is_stdout(output))
+        .filter_map(|output| Some((*output, FileId::of(output)?)))
+        .collect();
+    if outputs.is_empty() {
+        return Ok(());
+    }
+
+    for input in inputs {
+        let file = split_entry_path(input).map_or(input.as_str(), |(archive, _)| archive);
+        let Some(input_id) = FileId::of(Path::new(file)) else {
+            continue;
+        };
+
+        if let Some((output, _)) = outputs.iter().find(|(_, id)| id.same_file(&input_id)) {
+            bail!(
+                "Output file {} is also an input ({}), refusing to overwrite it",
+                output.display(),
+                input
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Opens the merged output at `path`, creating missing parent directories.
+/// `-` writes to standard output. Files are written under a temporary name
+/// in the same directory until [`Output::finish`].
+pub fn open_output(path: &Path, options: OutputOptions) -> Result<Output> {
+    #[cfg(not(feature = "zstd"))]
+    if options.compression == Some(OutputCompression::Zstd) {
+        bail!("This build has no `zstd` feature");
+    }
+
+    if is_stdout(path) {
+        return Ok(Output {
+            options,
+            state: Some(State::Idle(Box::new(BufWriter::new(stdout().lock())))),
+            written: 0,
+            content: Content::default(),
+            failed: false,
+            pending: None,
+        });
+    }
+
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    create_dir_all(parent)
+        .with_context(|| format!("Failed to create output directory ({})", parent.display()))?;
+
+    let (lock, lock_path) = lock_output(path, parent)?;
+    let prefix = hidden_name(path, ".");
+    let mut builder = Builder::new();
+    builder.prefix(&prefix).suffix(".tmp");
+    // Temporary files are private, the output gets the mode of any new file
+    // under the umask.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        builder.permissions(std::fs::Permissions::from_mode(0o666));
+    }
+    let temp = builder
+        .tempfile_in(parent)
+        .with_context(|| format!("Failed to open output file ({})", path.display()))?;
+    let (file, temp) = temp.into_parts();
+
+    Ok(Output {
+        options,
+        state: Some(State::Idle(Box::new(BufWriter::new(file.try_clone()?)))),
+        written: 0,
+        content: Content::default(),
+        failed: false,
+        pending: Some(Pending {
+            file,
+            _lock: lock,
+            lock_path,
+            temp: Some(temp),
+            path: path.to_owned(),
+            keep_partial: options.keep_partial,
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use flate2::read::MultiGzDecoder;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::fs::{read_dir, File};
+    use std::io::{self, Read, Write};
+
+    use super::{check_outputs, family_outputs, open_output, OutputCompression, OutputOptions};
+    use crate::decompress::Encoding;
+    use crate::sort::Family;
+    use anyhow::Result;
+
+    fn family(name: &str) -> Family {
+        Family {
+            name: String::from(name),
+            files: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn family_outputs_test() -> Result<()> {
+        let families = vec![
+            family("/var/log/auth.log"),
+            family("/var/log/nginx/access.log"),
+            family("/var/log/syslog"),
+        ];
+        let expected = vec![
+            PathBuf::from("out/auth.log"),
+            PathBuf::from("out/nginx/access.log"),
+            PathBuf::from("out/syslog"),
+        ];
+
+        assert_eq!(expected, family_outputs(&families, Path::new("out"))?);
+        Ok(())
+    }
+
+    #[test]
+    fn family_outputs_archive_test() -> Result<()> {
+        let families = vec![
+            family("/tmp/bundle.tar.gz!var/log/syslog"),
+            family("/tmp/bundle.tar.gz!var/log/nginx/access.log"),
+        ];
+        let expected = vec![
+            PathBuf::from("out/syslog"),
+            PathBuf::from("out/nginx/access.log"),
+        ];
+
+        assert_eq!(expected, family_outputs(&families, Path::new("out"))?);
+        Ok(())
+    }
+
+    #[test]
+    fn family_outputs_single_test() -> Result<()> {
+        let families = vec![family("../logs/app.log")];
+
+        assert_eq!(
+            vec![PathBuf::from("out/app.log")],
+            family_outputs(&families, Path::new("out"))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn gzip_output_test() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("merged.gz");
+
+        let mut member = GzEncoder::new(Vec::new(), Compression::default());
+        member.write_all(b"copied\n")?;
+        let member = member.finish()?;
+
+        let options = OutputOptions {
+            compression: Some(OutputCompression::Gzip),
+            level: Some(1),
+            keep_partial: false,
+        };
+        let mut output = open_output(&path, options)?;
+        output.write_all(b"first\n")?;
+        // Two members back to back, as `cat a.gz b.gz` makes.
+        let members = [member.as_slice(), member.as_slice()].concat();
+        let encoding = output.copy_gzip(&mut members.as_slice())?;
+        output.write_all(b"last\n")?;
+        output.finish()?;
+
+        assert_eq!(
+            encoding,
+            Encoding::Compressed {
+                codec: "gzip",
+                members: Some(2)
+            }
+        );
+        let mut merged = String::new();
+        MultiGzDecoder::new(File::open(&path)?).read_to_string(&mut merged)?;
+        assert_eq!(merged, "first\ncopied\ncopied\nlast\n");
+        Ok(())
+    }
+
+    #[test]
+    fn output_level_test() {
+        let options = |compression, level| OutputOptions {
+            compression: Some(compression),
+            level: Some(level),
+            keep_partial: false,
+        };
+
+        assert!(options(OutputCompression::Gzip, 9).validate().is_ok());
+        assert!(options(OutputCompression::Gzip, 10).validate().is_err());
+        assert!(options(OutputCompression::Zstd, 0).validate().is_err());
+        assert!(options(OutputCompression::Zstd, 22).validate().is_ok());
+    }
+
+    #[test]
+    fn unfinished_output_test() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let temp_files = || -> Result<usize> {
+            Ok(std::fs::read_dir(dir.path())?
+                .filter(|entry| {
+                    let entry = entry.as_ref().unwrap();
+                    entry.file_name().to_string_lossy().ends_with(".tmp")
+                })
+                .count())
+        };
+        let path = dir.path().join("merged.log");
+        std::fs::write(&path, "previous\n")?;
+
+        let mut output = open_output(&path, OutputOptions::default())?;
+        output.write_all(b"partial\n")?;
+        drop(output);
+        assert_eq!(std::fs::read_to_string(&path)?, "previous\n");
+        assert_eq!(temp_files()?, 0);
+
+        let options = OutputOptions {
+            keep_partial: true,
+            ..OutputOptions::default()
+        };
+        let mut output = open_output(&path, options)?;
+        output.write_all(b"partial\n")?;
+        drop(output);
+        assert_eq!(std::fs::read_to_string(&path)?, "partial\n");
+
+        let mut output = open_output(&path, OutputOptions::default())?;
+        output.write_all(b"complete\n")?;
+        output.finish()?;
+        assert_eq!(std::fs::read_to_string(&path)?, "complete\n");
+        assert_eq!(temp_files()?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn check_outputs_test() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let input = dir.path().join("app.log.1");
+        std::fs::write(&input, "line\n")?;
+        std::fs::hard_link(&input, dir.path().join("linked"))?;
+        let inputs = vec![input.to_str().unwrap().to_owned()];
+
+        std::fs::create_dir(dir.path().join("sub"))?;
+        let aliased = dir.path().join("sub/../app.log.1");
+        assert!(check_outputs(&[&aliased], &inputs).is_err());
+        assert!(check_outputs(&[&dir.path().join("linked")], &inputs).is_err());
+        assert!(check_outputs(&[&dir.path().join("merged.log")], &inputs).is_ok());
+        assert!(check_outputs(&[Path::new("-")], &inputs).is_ok());
+
+        // Standard input is no file to clash with.
+        let with_stdin = vec![String::from("-"), inputs[0].clone()];
+        assert!(check_outputs(&[&dir.path().join("merged.log")], &with_stdin).is_ok());
+        assert!(check_outputs(&[Path::new("-")], &with_stdin).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn stdout_output_test() -> Result<()> {
+        // `-` is standard output, not a file of that name.
+        let output = open_output(Path::new("-"), OutputOptions::default())?;
+        output.finish()?;
+        assert!(!Path::new("-").exists());
+        assert!(!Path::new(".-.lock").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn output_lock_test() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("merged.log");
+
+        let output = open_output(&path, OutputOptions::default())?;
+        assert!(open_output(&path, OutputOptions::default()).is_err());
+        output.finish()?;
+        open_output(&path, OutputOptions::default())?.finish()?;
+
+        // Only the output is left behind.
+        let names: Vec<_> = read_dir(dir.path())?
+            .map(|entry| entry.map(|entry| entry.file_name()))
+            .collect::<io::Result<_>>()?;
+        assert_eq!(names, ["merged.log"]);
+        Ok(())
+    }
+
+    #[test]
+    fn end_line_test() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("merged.gz");
+
+        let mut member = GzEncoder::new(Vec::new(), Compression::default());
+        member.write_all(b"copied without newline")?;
+        let member = member.finish()?;
+
+        let options = OutputOptions {
+            compression: Some(OutputCompression::Gzip),
+            ..OutputOptions::default()
+        };
+        let mut output = open_output(&path, options)?;
+        output.end_line()?;
+        output.write_all(b"cut after a crash")?;
+        output.end_line()?;
+        output.copy_gzip(&mut member.as_slice())?;
+        output.end_line()?;
+        output.write_all(b"terminated\n")?;
+        output.end_line()?;
+        output.finish()?;
+
+        let mut merged = String::new();
+        MultiGzDecoder::new(File::open(&path)?).read_to_string(&mut merged)?;
+        assert_eq!(
+            merged,
+            "cut after a crash\ncopied without newline\nterminated\n"
+        );
+        Ok(())
+    }
+}
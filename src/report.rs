@@ -0,0 +1,185 @@
+use std::io::{self, stderr, Write};
+use std::process::ExitCode;
+
+use crate::decompress::{Damage, Encoding};
+use crate::index::Slice;
+
+/// Exit code of a run where some inputs failed or lost data to damage but
+/// others made it into the output.
+pub const PARTIAL_FAILURE: u8 = 2;
+
+/// What happened to one input file.
+#[derive(Debug)]
+pub struct FileReport {
+    pub path: String,
+    /// Bytes written to the output, including those salvaged from a file
+    /// that failed halfway.
+    pub bytes: u64,
+    /// How the file was stored, or why it could not be read to the end.
+    pub result: Result<Encoding, String>,
+    /// Damaged stretches skipped while recovering the file.
+    pub damage: Vec<Damage>,
+    /// Where the content landed in the uncompressed output.
+    pub slice: Slice,
+}
+
+impl FileReport {
+    /// Made it into the output in full.
+    fn is_complete(&self) -> bool {
+        self.result.is_ok() && self.damage.is_empty()
+    }
+
+    fn status(&self) -> &'static str {
+        match (&self.result, self.bytes) {
+            (Ok(_), _) if self.damage.is_empty() => "ok",
+            (Ok(_), _) => "recovered",
+            (Err(_), 0) => "failed",
+            (Err(_), _) => "partial",
+        }
+    }
+
+    fn details(&self) -> String {
+        match &self.result {
+            Ok(encoding) if self.damage.is_empty() => encoding.to_string(),
+            Ok(encoding) => {
+                let lost: u64 = self.damage.iter().map(|damage| damage.skipped).sum();
+                let offsets: Vec<String> = self
+                    .damage
+                    .iter()
+                    .map(|damage| damage.offset.to_string())
+                    .collect();
+                format!(
+                    "{}, lost {} compressed bytes at offset(s) {}",
+                    encoding,
+                    lost,
+                    offsets.join(", ")
+                )
+            }
+            Err(error) => error.clone(),
+        }
+    }
+}
+
+/// Alignment of a table column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+/// Writes `rows` under `header` to `out`, with the columns padded to their
+/// widest cell. The last column is left as it is.
+pub fn write_table(
+    out: &mut dyn Write,
+    header: &[(&str, Align)],
+    rows: &[Vec<String>],
+) -> io::Result<()> {
+    let header_row: Vec<String> = header.iter().map(|(name, _)| name.to_string()).collect();
+
+    let mut widths = vec![0; header.len()];
+    for row in rows.iter().chain([&header_row]) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    for row in [&header_row].into_iter().chain(rows) {
+        let mut line = String::new();
+        for (column, cell) in row.iter().enumerate() {
+            if column > 0 {
+                line.push_str("  ");
+            }
+            let width = widths[column];
+            match header[column].1 {
+                _ if column + 1 == row.len() => line.push_str(cell),
+                Align::Left => line.push_str(&format!("{:<width$}", cell, width = width)),
+                Align::Right => line.push_str(&format!("{:>width$}", cell, width = width)),
+            }
+        }
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
+
+/// Prints one row per input file to standard error.
+pub fn print_summary(reports: &[FileReport]) -> io::Result<()> {
+    let rows: Vec<Vec<String>> = reports
+        .iter()
+        .map(|report| {
+            vec![
+                report.path.clone(),
+                report.status().to_owned(),
+                report.bytes.to_string(),
+                report.details(),
+            ]
+        })
+        .collect();
+
+    write_table(
+        &mut stderr().lock(),
+        &[
+            ("FILE", Align::Left),
+            ("STATUS", Align::Left),
+            ("BYTES", Align::Right),
+            ("DETAILS", Align::Left),
+        ],
+        &rows,
+    )
+}
+
+/// Success when every file was merged in full, [`PARTIAL_FAILURE`] when
+/// some were not, and plain failure when none was merged at all.
+pub fn exit_code(reports: &[FileReport]) -> ExitCode {
+    if reports.iter().all(FileReport::is_complete) {
+        ExitCode::SUCCESS
+    } else if reports.iter().any(|report| report.result.is_ok()) {
+        ExitCode::from(PARTIAL_FAILURE)
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::ExitCode;
+
+    use super::{exit_code, FileReport, PARTIAL_FAILURE};
+    use crate::decompress::{Damage, Encoding};
+    use crate::index::Slice;
+
+    fn report(failed: bool) -> FileReport {
+        FileReport {
+            path: String::from("app.log.1.gz"),
+            bytes: 10,
+            result: if failed {
+                Err(String::from("corrupt deflate stream"))
+            } else {
+                Ok(Encoding::Plain)
+            },
+            damage: Vec::new(),
+            slice: Slice::default(),
+        }
+    }
+
+    #[test]
+    fn exit_code_test() {
+        assert_eq!(
+            exit_code(&[report(false), report(false)]),
+            ExitCode::SUCCESS
+        );
+        assert_eq!(
+            exit_code(&[report(false), report(true)]),
+            ExitCode::from(PARTIAL_FAILURE)
+        );
+        assert_eq!(exit_code(&[report(true), report(true)]), ExitCode::FAILURE);
+
+        let mut recovered = report(false);
+        recovered.damage.push(Damage {
+            offset: 100,
+            decoded: 4000,
+            skipped: 20,
+            error: String::from("corrupt deflate stream"),
+        });
+        assert_eq!(exit_code(&[recovered]), ExitCode::from(PARTIAL_FAILURE));
+    }
+}
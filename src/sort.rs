@@ -0,0 +1,853 @@
+use anyhow::{bail, Context, Result};
+use chrono::{Datelike, Duration, Months, NaiveDate};
+use clap::ValueEnum;
+use regex::{Captures, Regex};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{BufRead, BufReader};
+use std::path::is_separator;
+use std::sync::OnceLock;
+
+use crate::decompress::{decoded_reader, gzip_mtime};
+use crate::input::{modified, with_input, ENTRY_SEPARATOR, STDIO_PATH};
+use crate::timestamp::find_timestamp;
+
+/// Order in which rotated files are written into the merged output.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergeOrder {
+    /// Oldest rotation first, so timestamps only go forward.
+    OldestFirst,
+    /// Newest rotation first.
+    NewestFirst,
+}
+
+/// Where the order of files comes from.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortBy {
+    /// Rotation scheme of the file name.
+    Name,
+    /// MTIME field of the gzip header.
+    GzipMtime,
+    /// Modification time of the file.
+    FsMtime,
+    /// First timestamp found in the decompressed content.
+    Content,
+}
+
+#[derive(Debug, Clone)]
+pub struct SortOptions {
+    pub order: MergeOrder,
+    /// User supplied `--sort-pattern`, tried before the built-in schemes.
+    pub pattern: Option<Regex>,
+    pub sort_by: SortBy,
+    /// Fail on gaps in the rotation sequence instead of warning about them.
+    pub strict: bool,
+}
+
+/// Chronological position of a file inside its family: a smaller key is an
+/// older file. Fields are compared in declaration order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SortKey {
+    /// Milliseconds since the epoch picked by `--sort-by`, `None` when
+    /// sorting by name only.
+    pub timestamp: Option<i64>,
+    /// The live, not yet rotated file is always the newest one.
+    pub live: bool,
+    /// Date digits, e.g. 20220715 for `2022-07-15`.
+    pub date: Option<u64>,
+    /// Time of day or epoch seconds digits.
+    pub time: Option<u64>,
+    /// Counter that grows with time, like log4j's `%i`.
+    pub seq: Option<u64>,
+    /// logrotate number, a higher number is an older file.
+    pub index: Option<Reverse<u64>>,
+}
+
+impl fmt::Display for SortKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(timestamp) = self.timestamp {
+            parts.push(format!("timestamp {}", timestamp));
+        }
+        if self.live {
+            parts.push(String::from("live file"));
+        }
+        if let Some(date) = self.date {
+            parts.push(format!("date {}", date));
+        }
+        if let Some(time) = self.time {
+            parts.push(format!("time {}", time));
+        }
+        if let Some(seq) = self.seq {
+            parts.push(format!("seq {}", seq));
+        }
+        if let Some(Reverse(index)) = self.index {
+            parts.push(format!("index {}", index));
+        }
+
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Position of a file inside its rotation family.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rotation {
+    /// Path without the rotation suffix: `/var/log/app.log` for
+    /// `/var/log/app.log.3.gz`.
+    pub family: String,
+    pub key: SortKey,
+}
+
+const COMPRESSED_EXTENSIONS: [&str; 8] =
+    [".gz", ".zst", ".zstd", ".xz", ".bz2", ".lz4", ".br", ".Z"];
+
+/// Built-in naming schemes, tried in order against the file name with its
+/// compression extension stripped. `family` and the optional `ext` form the
+/// family name, the other groups form the sort key.
+const SCHEMES: [&str; 5] = [
+    // logrotate dateext: app.log-20220715, app.log-2022-07-15-1657843200
+    r"^(?P<family>.+?)-(?P<date>\d{4}-?\d{2}-?\d{2})(?:-(?P<time>\d+))?$",
+    // logrotate dateext with dateformat .%s or -%s: app.log.1657843200,
+    // app.log-1657843200. Only epoch seconds since 2001, so that a plain
+    // logrotate number is not taken for one.
+    r"^(?P<family>.+?)[.-](?P<time>1\d{9})$",
+    // logrotate and Python's RotatingFileHandler: app.log.3
+    r"^(?P<family>.+?)\.(?P<index>\d+)$",
+    // log4j: app-2022-07-15.3.log, app-2022-07-15-10-30.log
+    r"^(?P<family>.+?)-(?P<date>\d{4}-\d{2}-\d{2})(?:-(?P<time>\d{2}(?:-\d{2}){0,2}))?(?:\.(?P<seq>\d+))?(?P<ext>\.[A-Za-z]\w*)$",
+    // Python's TimedRotatingFileHandler: app.log.2022-07-15_10-30-00
+    r"^(?P<family>.+?)\.(?P<date>\d{4}-\d{2}-\d{2})(?:_(?P<time>\d{2}(?:-\d{2}){0,2}))?$",
+];
+
+fn schemes() -> &'static [Regex] {
+    static SCHEMES_CELL: OnceLock<Vec<Regex>> = OnceLock::new();
+    SCHEMES_CELL.get_or_init(|| {
+        SCHEMES
+            .iter()
+            .map(|scheme| Regex::new(scheme).expect("built-in scheme is a valid regex"))
+            .collect()
+    })
+}
+
+/// Parses the digits of a capture group, ignoring `-`, `_`, `.` and `:`
+/// separators.
+fn capture_number(captures: &Captures, name: &str) -> Result<Option<u64>> {
+    captures
+        .name(name)
+        .map(|value| {
+            let digits: String = value
+                .as_str()
+                .chars()
+                .filter(|c| !matches!(c, '-' | '_' | '.' | ':'))
+                .collect();
+            digits
+                .parse::<u64>()
+                .with_context(|| format!("Wrong {} in filename ({})", name, value.as_str()))
+        })
+        .transpose()
+}
+
+fn key_from_captures(captures: &Captures) -> Result<SortKey> {
+    Ok(SortKey {
+        timestamp: None,
+        live: false,
+        date: capture_number(captures, "date")?,
+        time: capture_number(captures, "time")?,
+        seq: capture_number(captures, "seq")?,
+        index: capture_number(captures, "index")?.map(Reverse),
+    })
+}
+
+/// Splits `path` into its family and sort key. Files that match neither
+/// `pattern` nor a built-in scheme are treated as the live file.
+///
+/// The named captures `date`, `time`, `seq` and `index` of `pattern` build
+/// the key, and the family is the file name with the match removed.
+pub fn parse_rotation(path: &str, pattern: Option<&Regex>) -> Result<Rotation> {
+    // Entries of a bundle are named `logs.tar.gz!dir/app.log.1.gz`.
+    let name_start = path
+        .rfind(|c| is_separator(c) || c == ENTRY_SEPARATOR)
+        .map_or(0, |index| index + 1);
+    let (dir, file_name) = path.split_at(name_start);
+    if file_name.is_empty() {
+        bail!("Wrong filename format! ({})", path);
+    }
+
+    let name = COMPRESSED_EXTENSIONS
+        .iter()
+        .find_map(|extension| file_name.strip_suffix(extension))
+        .unwrap_or(file_name);
+
+    if let Some(captures) = pattern.and_then(|pattern| pattern.captures(name)) {
+        let matched = captures.get(0).expect("group 0 is always present");
+        return Ok(Rotation {
+            family: format!(
+                "{}{}{}",
+                dir,
+                &name[..matched.start()],
+                &name[matched.end()..]
+            ),
+            key: key_from_captures(&captures)?,
+        });
+    }
+
+    for scheme in schemes() {
+        if let Some(captures) = scheme.captures(name) {
+            let ext = captures.name("ext").map_or("", |ext| ext.as_str());
+            return Ok(Rotation {
+                family: format!("{}{}{}", dir, &captures["family"], ext),
+                key: key_from_captures(&captures)?,
+            });
+        }
+    }
+
+    Ok(Rotation {
+        family: format!("{}{}", dir, name),
+        key: SortKey {
+            live: true,
+            ..SortKey::default()
+        },
+    })
+}
+
+/// Number of decompressed lines searched for a timestamp with
+/// `--sort-by content`.
+const CONTENT_SCAN_LINES: usize = 1000;
+
+/// First timestamp `find` finds in the decoded content of `path`, searching
+/// its first [`CONTENT_SCAN_LINES`] lines.
+pub fn content_timestamp(
+    path: &str,
+    default_year: i32,
+    find: fn(&str, i32) -> Option<i64>,
+) -> Result<Option<i64>> {
+    with_input(path, |reader| {
+        let reader = BufReader::new(decoded_reader(reader, path)?);
+        for line in reader.split(b'\n').take(CONTENT_SCAN_LINES) {
+            let line = line.with_context(|| format!("Failed to read archive file ({})", path))?;
+            if let Some(timestamp) = find(&String::from_utf8_lossy(&line), default_year) {
+                return Ok(Some(timestamp));
+            }
+        }
+
+        Ok(None)
+    })
+}
+
+/// Timestamp of `path` according to `sort_by`, in milliseconds since the
+/// epoch. Files without a gzip MTIME or a timestamp in their content fall
+/// back to their modification time, and so does standard input, which can
+/// only be read once.
+fn file_timestamp(path: &str, sort_by: SortBy) -> Result<Option<i64>> {
+    if sort_by == SortBy::Name {
+        return Ok(None);
+    }
+
+    let modified = modified(path)?;
+    if path == STDIO_PATH {
+        return Ok(Some(modified.timestamp_millis()));
+    }
+
+    let timestamp = match sort_by {
+        SortBy::GzipMtime => {
+            with_input(path, |reader| gzip_mtime(reader))?.map(|mtime| i64::from(mtime) * 1000)
+        }
+        SortBy::Content => content_timestamp(path, modified.year(), find_timestamp)?,
+        SortBy::Name | SortBy::FsMtime => None,
+    };
+
+    Ok(Some(
+        timestamp.unwrap_or_else(|| modified.timestamp_millis()),
+    ))
+}
+
+/// Rotations missing between two files of a family.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gap {
+    pub after: String,
+    pub before: String,
+    /// First and last missing logrotate number or date, as they would
+    /// appear in names.
+    pub first: String,
+    pub last: String,
+    /// Number of missing rotations from `first` to `last`.
+    pub count: u64,
+}
+
+impl fmt::Display for Gap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.count == 1 {
+            write!(f, "missing rotation {}", self.first)?;
+        } else {
+            write!(
+                f,
+                "missing rotations {} to {} ({})",
+                self.first, self.last, self.count
+            )?;
+        }
+        write!(f, " between {} and {}", self.after, self.before)
+    }
+}
+
+fn parse_date(date: u64) -> Option<NaiveDate> {
+    NaiveDate::from_ymd_opt(
+        (date / 10000) as i32,
+        (date / 100 % 100) as u32,
+        (date % 100) as u32,
+    )
+}
+
+fn month_number(date: NaiveDate) -> i64 {
+    i64::from(date.year()) * 12 + i64::from(date.month0())
+}
+
+/// Logrotate numbers from here on are taken for epoch seconds rather than
+/// for a position in the rotation.
+const MAX_INDEX: u64 = 1_000_000_000;
+
+/// Finds holes in the logrotate numbers of `files`, counting from 1 when
+/// the live file is among them, and, for files named by date only, in their
+/// dates. Dates are expected at the shortest interval seen between two
+/// files, so that weekly and monthly rotations are not taken for daily ones
+/// with gaps.
+fn find_gaps(files: &[(SortKey, &String)]) -> Vec<Gap> {
+    let mut gaps = Vec::new();
+
+    let mut indices: Vec<(u64, &String)> = files
+        .iter()
+        .filter_map(|(key, file)| Some((key.index?.0, *file)))
+        .filter(|(index, _)| *index < MAX_INDEX)
+        .collect();
+    if !indices.is_empty() {
+        if let Some((_, file)) = files.iter().find(|(key, _)| key.live) {
+            indices.push((0, *file));
+        }
+    }
+    indices.sort();
+    for pair in indices.windows(2) {
+        let ((newer, newer_file), (older, older_file)) = (pair[0], pair[1]);
+        if older > newer + 1 {
+            gaps.push(Gap {
+                after: older_file.to_owned(),
+                before: newer_file.to_owned(),
+                first: (older - 1).to_string(),
+                last: (newer + 1).to_string(),
+                count: older - newer - 1,
+            });
+        }
+    }
+
+    let mut dates: Vec<(NaiveDate, &String)> = files
+        .iter()
+        .filter(|(key, _)| key.time.is_none() && key.seq.is_none())
+        .filter_map(|(key, file)| Some((parse_date(key.date?)?, *file)))
+        .collect();
+    dates.sort();
+    dates.dedup_by_key(|(date, _)| *date);
+
+    let days: Vec<i64> = dates
+        .windows(2)
+        .map(|pair| (pair[1].0 - pair[0].0).num_days())
+        .collect();
+    let Some(&interval) = days.iter().min() else {
+        return gaps;
+    };
+    let monthly = interval >= 28;
+
+    for pair in dates.windows(2) {
+        let ((earlier, earlier_file), (later, later_file)) = (pair[0], pair[1]);
+        // The n-th rotation after `earlier`.
+        let nth = |n: i64| {
+            if monthly {
+                earlier.checked_add_months(Months::new(n as u32))
+            } else {
+                earlier.checked_add_signed(Duration::days(n * interval))
+            }
+        };
+        let count = if monthly {
+            month_number(later) - month_number(earlier) - 1
+        } else {
+            ((later - earlier).num_days() - 1) / interval
+        };
+
+        if count > 0 {
+            if let (Some(first), Some(last)) = (nth(1), nth(count)) {
+                gaps.push(Gap {
+                    after: earlier_file.to_owned(),
+                    before: later_file.to_owned(),
+                    first: first.to_string(),
+                    last: last.to_string(),
+                    count: count as u64,
+                });
+            }
+        }
+    }
+
+    gaps
+}
+
+/// Orders the files of a single rotation family. Two files with the same
+/// sort key are reported as an error rather than dropped. Gaps in the
+/// rotation sequence are warned about, or fail with `strict`.
+pub fn sort_files(files: &[String], options: &SortOptions) -> Result<Vec<String>> {
+    let mut by_key: BTreeMap<SortKey, &String> = BTreeMap::new();
+    let mut named: Vec<(SortKey, &String)> = Vec::with_capacity(files.len());
+    for file in files {
+        let mut key = parse_rotation(file, options.pattern.as_ref())?.key;
+        named.push((key.clone(), file));
+        key.timestamp = file_timestamp(file, options.sort_by)?;
+        if let Some(previous) = by_key.insert(key.clone(), file) {
+            bail!(
+                "Files {} and {} have the same position in the rotation ({})",
+                previous,
+                file,
+                key
+            );
+        }
+    }
+
+    let gaps = find_gaps(&named);
+    if options.strict && !gaps.is_empty() {
+        let gaps: Vec<String> = gaps.iter().map(|gap| gap.to_string()).collect();
+        bail!("Gaps in the rotation sequence: {}", gaps.join("; "));
+    }
+    for gap in gaps {
+        eprintln!("Warning: {}", gap);
+    }
+
+    let sorted = by_key.into_values().cloned();
+    Ok(match options.order {
+        MergeOrder::OldestFirst => sorted.collect(),
+        MergeOrder::NewestFirst => sorted.rev().collect(),
+    })
+}
+
+/// Rotated files of one log, in merge order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Family {
+    pub name: String,
+    pub files: Vec<String>,
+}
+
+/// Groups `files` by family and sorts each family on its own. Families are
+/// returned ordered by name.
+pub fn group_families(files: &[String], options: &SortOptions) -> Result<Vec<Family>> {
+    let mut families: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for file in files {
+        families
+            .entry(parse_rotation(file, options.pattern.as_ref())?.family)
+            .or_default()
+            .push(file.to_owned());
+    }
+
+    families
+        .into_iter()
+        .map(|(name, files)| {
+            Ok(Family {
+                files: sort_files(&files, options)?,
+                name,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        find_gaps, group_families, parse_rotation, sort_files, MergeOrder, SortBy, SortKey,
+        SortOptions,
+    };
+    use anyhow::Result;
+    use flate2::{Compression, GzBuilder};
+    use regex::Regex;
+    use std::cmp::Reverse;
+    use std::fs::write;
+    use std::io::Write;
+
+    fn oldest_first() -> SortOptions {
+        SortOptions {
+            order: MergeOrder::OldestFirst,
+            pattern: None,
+            sort_by: SortBy::Name,
+            strict: false,
+        }
+    }
+
+    fn newest_first() -> SortOptions {
+        SortOptions {
+            order: MergeOrder::NewestFirst,
+            pattern: None,
+            sort_by: SortBy::Name,
+            strict: false,
+        }
+    }
+
+    #[test]
+    fn sort_inputs_test() -> Result<()> {
+        let inputs = vec![
+            String::from("a.log.4.gz"),
+            String::from("a.log.1.gz"),
+            String::from("a.log.30.gz"),
+            String::from("a.log.2.gz"),
+        ];
+        let expected = vec![
+            String::from("a.log.30.gz"),
+            String::from("a.log.4.gz"),
+            String::from("a.log.2.gz"),
+            String::from("a.log.1.gz"),
+        ];
+        assert_eq!(expected, sort_files(&inputs, &oldest_first())?);
+
+        let expected = vec![
+            String::from("a.log.1.gz"),
+            String::from("a.log.2.gz"),
+            String::from("a.log.4.gz"),
+            String::from("a.log.30.gz"),
+        ];
+        assert_eq!(expected, sort_files(&inputs, &newest_first())?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sort_delaycompress_test() -> Result<()> {
+        let inputs = vec![
+            String::from("app.log.2.gz"),
+            String::from("app.log"),
+            String::from("app.log.3.gz"),
+            String::from("app.log.1"),
+        ];
+        let expected = vec![
+            String::from("app.log.3.gz"),
+            String::from("app.log.2.gz"),
+            String::from("app.log.1"),
+            String::from("app.log"),
+        ];
+
+        assert_eq!(expected, sort_files(&inputs, &oldest_first())?);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_rotation_test() -> Result<()> {
+        let rotation = parse_rotation("/var/log/app.log.3.gz", None)?;
+        assert_eq!(rotation.family, "/var/log/app.log");
+        assert_eq!(rotation.key.index, Some(Reverse(3)));
+
+        let rotation = parse_rotation("/var/log/app.log", None)?;
+        assert_eq!(rotation.family, "/var/log/app.log");
+        assert!(rotation.key.live);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_rotation_schemes_test() -> Result<()> {
+        let cases = [
+            ("app.log-20220715.gz", "app.log", Some(20220715), None, None),
+            (
+                "app.log-2022-07-15-1657843200.gz",
+                "app.log",
+                Some(20220715),
+                Some(1657843200),
+                None,
+            ),
+            (
+                "app-2022-07-15.3.log.gz",
+                "app.log",
+                Some(20220715),
+                None,
+                Some(3),
+            ),
+            (
+                "app-2022-07-15.log.gz",
+                "app.log",
+                Some(20220715),
+                None,
+                None,
+            ),
+            ("app.log.2022-07-15", "app.log", Some(20220715), None, None),
+            (
+                "app.log.2022-07-15_10-30",
+                "app.log",
+                Some(20220715),
+                Some(1030),
+                None,
+            ),
+            (
+                "app.log.1657843200.gz",
+                "app.log",
+                None,
+                Some(1657843200),
+                None,
+            ),
+            (
+                "app.log-1657843200",
+                "app.log",
+                None,
+                Some(1657843200),
+                None,
+            ),
+        ];
+
+        for (path, family, date, time, seq) in cases {
+            let rotation = parse_rotation(path, None)?;
+            assert_eq!(rotation.family, family, "{}", path);
+            assert_eq!(
+                rotation.key,
+                SortKey {
+                    timestamp: None,
+                    live: false,
+                    date,
+                    time,
+                    seq,
+                    index: None
+                },
+                "{}",
+                path
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn sort_dateext_test() -> Result<()> {
+        let inputs = vec![
+            String::from("app.log"),
+            String::from("app.log-20220715.gz"),
+            String::from("app.log-20220801.gz"),
+            String::from("app.log-20220714.gz"),
+        ];
+        let expected = vec![
+            String::from("app.log-20220714.gz"),
+            String::from("app.log-20220715.gz"),
+            String::from("app.log-20220801.gz"),
+            String::from("app.log"),
+        ];
+
+        let options = SortOptions {
+            order: MergeOrder::OldestFirst,
+            pattern: None,
+            sort_by: SortBy::Name,
+            strict: false,
+        };
+        assert_eq!(expected, sort_files(&inputs, &options)?);
+
+        // dateformat .%s: a higher epoch is a newer file.
+        for separator in [".", "-"] {
+            let old = format!("app.log{}1657756800.gz", separator);
+            let new = format!("app.log{}1657843200.gz", separator);
+            let inputs = vec![String::from("app.log"), new.clone(), old.clone()];
+            let expected = vec![old, new, String::from("app.log")];
+            assert_eq!(expected, sort_files(&inputs, &options)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn sort_pattern_test() -> Result<()> {
+        let inputs = vec![
+            String::from("/logs/app_2022.07.15_2.txt.gz"),
+            String::from("/logs/app_2022.07.15_1.txt.gz"),
+            String::from("/logs/app_2022.07.14_9.txt.gz"),
+            String::from("/logs/app.txt"),
+        ];
+        let pattern = Regex::new(r"_(?P<date>\d{4}\.\d{2}\.\d{2})_(?P<seq>\d+)")?;
+
+        let rotation = parse_rotation(&inputs[0], Some(&pattern))?;
+        assert_eq!(rotation.family, "/logs/app.txt");
+        assert_eq!(rotation.key.date, Some(20220715));
+        assert_eq!(rotation.key.seq, Some(2));
+
+        let options = SortOptions {
+            order: MergeOrder::OldestFirst,
+            pattern: Some(pattern),
+            sort_by: SortBy::Name,
+            strict: false,
+        };
+        let expected = vec![
+            String::from("/logs/app_2022.07.14_9.txt.gz"),
+            String::from("/logs/app_2022.07.15_1.txt.gz"),
+            String::from("/logs/app_2022.07.15_2.txt.gz"),
+            String::from("/logs/app.txt"),
+        ];
+        assert_eq!(expected, sort_files(&inputs, &options)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sort_by_gzip_mtime_test() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut inputs = Vec::new();
+        for (name, mtime) in [("a.log.gz", 1657843200), ("b.log.gz", 1657756800)] {
+            let mut encoder = GzBuilder::new()
+                .mtime(mtime)
+                .write(Vec::new(), Compression::default());
+            encoder.write_all(b"line\n")?;
+            let path = dir.path().join(name);
+            write(&path, encoder.finish()?)?;
+            inputs.push(path.to_str().unwrap().to_owned());
+        }
+
+        let options = SortOptions {
+            order: MergeOrder::OldestFirst,
+            pattern: None,
+            sort_by: SortBy::GzipMtime,
+            strict: false,
+        };
+        let expected = vec![inputs[1].clone(), inputs[0].clone()];
+        assert_eq!(expected, sort_files(&inputs, &options)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sort_by_content_test() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut inputs = Vec::new();
+        for (name, content) in [
+            ("host1.log", "2022-07-15T10:00:00Z second\n"),
+            (
+                "host2.log",
+                "no timestamp here\n2022-07-14T10:00:00Z first\n",
+            ),
+            ("host3.log", "[16/Jul/2022:10:00:00 +0000] third\n"),
+        ] {
+            let path = dir.path().join(name);
+            write(&path, content)?;
+            inputs.push(path.to_str().unwrap().to_owned());
+        }
+
+        let options = SortOptions {
+            order: MergeOrder::OldestFirst,
+            pattern: None,
+            sort_by: SortBy::Content,
+            strict: false,
+        };
+        let expected = vec![inputs[1].clone(), inputs[0].clone(), inputs[2].clone()];
+        assert_eq!(expected, sort_files(&inputs, &options)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sort_stdin_test() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let rotated = dir.path().join("app.log.1");
+        write(&rotated, "rotated\n")?;
+        let inputs = vec![String::from("-"), rotated.to_str().unwrap().to_owned()];
+
+        // Standard input is not read ahead and counts as written just now.
+        let options = SortOptions {
+            sort_by: SortBy::Content,
+            ..oldest_first()
+        };
+        let expected = vec![inputs[1].clone(), inputs[0].clone()];
+        assert_eq!(expected, sort_files(&inputs, &options)?);
+        assert_eq!(expected, sort_files(&inputs, &oldest_first())?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sort_duplicate_rotation_test() {
+        let inputs = vec![
+            String::from("/a/x.log.1.gz"),
+            String::from("/a/x.log.2.gz"),
+            String::from("/b/x.log.1.gz"),
+        ];
+
+        let error = sort_files(&inputs, &oldest_first())
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("/a/x.log.1.gz"));
+        assert!(error.contains("/b/x.log.1.gz"));
+    }
+
+    #[test]
+    fn group_families_test() -> Result<()> {
+        let inputs = vec![
+            String::from("y.log.1.gz"),
+            String::from("x.log.1.gz"),
+            String::from("x.log"),
+            String::from("y.log.2.gz"),
+        ];
+
+        let families = group_families(&inputs, &oldest_first())?;
+        assert_eq!(families.len(), 2);
+        assert_eq!(families[0].name, "x.log");
+        assert_eq!(families[0].files, vec!["x.log.1.gz", "x.log"]);
+        assert_eq!(families[1].name, "y.log");
+        assert_eq!(families[1].files, vec!["y.log.2.gz", "y.log.1.gz"]);
+
+        Ok(())
+    }
+
+    fn gaps(names: &[&str]) -> Vec<(String, String, u64)> {
+        let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        let keys: Vec<(SortKey, &String)> = names
+            .iter()
+            .map(|name| (parse_rotation(name, None).unwrap().key, name))
+            .collect();
+        find_gaps(&keys)
+            .into_iter()
+            .map(|gap| (gap.first, gap.last, gap.count))
+            .collect()
+    }
+
+    #[test]
+    fn find_gaps_test() -> Result<()> {
+        let gap = |first: &str, last: &str, count| (first.to_owned(), last.to_owned(), count);
+        assert_eq!(
+            gaps(&[
+                "app.log.1.gz",
+                "app.log.6.gz",
+                "app.log.8.gz",
+                "app.log.11.gz"
+            ]),
+            vec![gap("5", "2", 4), gap("7", "7", 1), gap("10", "9", 2)]
+        );
+        assert!(gaps(&["app.log", "app.log.1", "app.log.2.gz"]).is_empty());
+        // The live file is followed by app.log.1.
+        assert_eq!(
+            gaps(&["app.log", "app.log.2.gz", "app.log.3.gz"]),
+            vec![gap("1", "1", 1)]
+        );
+        assert_eq!(
+            gaps(&[
+                "app.log-20220714",
+                "app.log-20220715",
+                "app.log-20220718.gz"
+            ]),
+            vec![gap("2022-07-16", "2022-07-17", 2)]
+        );
+        // Weekly and monthly rotations.
+        assert!(gaps(&["app.log-20220701", "app.log-20220708", "app.log-20220715"]).is_empty());
+        assert_eq!(
+            gaps(&["app.log-20220101", "app.log-20220201", "app.log-20220501"]),
+            vec![gap("2022-03-01", "2022-04-01", 2)]
+        );
+        // Far apart files are reported as a single range.
+        assert_eq!(
+            gaps(&["app.log-19700101", "app.log-19700102", "app.log-20220715"]),
+            vec![gap("1970-01-03", "2022-07-14", 19186)]
+        );
+        // Epoch seconds are not logrotate numbers.
+        assert!(gaps(&["app.log.1", "app.log.1657843200"]).is_empty());
+        assert!(gaps(&["app.log.1657756800", "app.log.1657843200"]).is_empty());
+
+        let inputs = vec![String::from("app.log.1.gz"), String::from("app.log.3.gz")];
+        assert!(sort_files(&inputs, &oldest_first()).is_ok());
+        let strict = SortOptions {
+            strict: true,
+            ..oldest_first()
+        };
+        let error = sort_files(&inputs, &strict).unwrap_err().to_string();
+        assert!(error.contains("missing rotation 2 "), "{}", error);
+
+        Ok(())
+    }
+}
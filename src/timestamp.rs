@@ -0,0 +1,256 @@
+use anyhow::{bail, Result};
+use chrono::{Datelike, Duration, FixedOffset, NaiveDate, TimeZone, Utc};
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Recognized formats. Every regex names the same groups so that one
+/// conversion handles them all. The flag tells whether the timestamp also
+/// starts a line when it comes after other fields.
+const FORMATS: [(&str, bool); 4] = [
+    // ISO 8601 and RFC 3339: 2022-07-15T10:00:01.123+02:00, seconds optional
+    (
+        r"(?P<year>\d{4})-(?P<month>\d{2})-(?P<day>\d{2})[T ](?P<hour>\d{2}):(?P<minute>\d{2})(?::(?P<second>\d{2})(?:[.,](?P<fraction>\d{1,9}))?)?(?P<offset>Z|[+-]\d{2}:?\d{2})?",
+        false,
+    ),
+    // Apache: [15/Jul/2022:10:00:01 +0000], behind the client address
+    (
+        r"\[(?P<day>\d{2})/(?P<month>[A-Z][a-z]{2})/(?P<year>\d{4}):(?P<hour>\d{2}):(?P<minute>\d{2}):(?P<second>\d{2}) (?P<offset>[+-]\d{4})\]",
+        true,
+    ),
+    // syslog: Jul 15 10:00:01
+    (
+        r"\b(?P<month>[A-Z][a-z]{2}) +(?P<day>\d{1,2}) (?P<hour>\d{2}):(?P<minute>\d{2}):(?P<second>\d{2})\b",
+        false,
+    ),
+    // Epoch seconds or milliseconds, only at the start of a line since any
+    // long number would do elsewhere: 1657879201.250, [1657879201250]
+    (r"^\[?(?P<epoch>\d{10}(?:\.\d{1,9})?|\d{13})\b", false),
+];
+
+fn formats() -> &'static [(Regex, bool)] {
+    static FORMATS_CELL: OnceLock<Vec<(Regex, bool)>> = OnceLock::new();
+    FORMATS_CELL.get_or_init(|| {
+        FORMATS
+            .iter()
+            .map(|(format, anywhere)| {
+                let regex = Regex::new(format).expect("built-in format is a valid regex");
+                (regex, *anywhere)
+            })
+            .collect()
+    })
+}
+
+fn parse_offset(offset: &str) -> Option<FixedOffset> {
+    if offset == "Z" {
+        return FixedOffset::east_opt(0);
+    }
+
+    let sign = if offset.starts_with('-') { -1 } else { 1 };
+    let digits: String = offset[1..].chars().filter(|c| *c != ':').collect();
+    let hours: i32 = digits.get(..2)?.parse().ok()?;
+    let minutes: i32 = digits.get(2..)?.parse().ok()?;
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+fn to_millis(captures: &Captures, default_year: i32) -> Option<i64> {
+    if let Some(epoch) = captures.name("epoch") {
+        let epoch = epoch.as_str();
+        return match epoch.split_once('.') {
+            Some((seconds, fraction)) => {
+                let millis = format!("{:0<3}", &fraction[..fraction.len().min(3)]);
+                Some(seconds.parse::<i64>().ok()? * 1000 + millis.parse::<i64>().ok()?)
+            }
+            None if epoch.len() == 13 => epoch.parse().ok(),
+            None => Some(epoch.parse::<i64>().ok()? * 1000),
+        };
+    }
+
+    let number = |name: &str| captures.name(name)?.as_str().parse::<u32>().ok();
+
+    let year = match captures.name("year") {
+        Some(year) => year.as_str().parse().ok()?,
+        None => default_year,
+    };
+    let month = match number("month") {
+        Some(month) => month,
+        None => MONTHS.iter().position(|m| *m == &captures["month"])? as u32 + 1,
+    };
+    let fraction = captures.name("fraction").map_or(0, |fraction| {
+        let digits = fraction.as_str();
+        digits.parse::<u32>().unwrap_or(0) * 10u32.pow(9 - digits.len() as u32)
+    });
+
+    let time = NaiveDate::from_ymd_opt(year, month, number("day")?)?.and_hms_nano_opt(
+        number("hour")?,
+        number("minute")?,
+        number("second").unwrap_or(0),
+        fraction,
+    )?;
+
+    let millis = match captures.name("offset") {
+        Some(offset) => parse_offset(offset.as_str())?
+            .from_local_datetime(&time)
+            .single()?
+            .timestamp_millis(),
+        None => Utc.from_utc_datetime(&time).timestamp_millis(),
+    };
+    Some(millis)
+}
+
+/// Matches of every format in `line`, the leading ones only with
+/// `leading`: those with nothing before them but punctuation like `[` or a
+/// syslog priority `<13>`.
+fn matches(line: &str, leading: bool) -> impl Iterator<Item = Captures<'_>> {
+    formats().iter().filter_map(move |(format, anywhere)| {
+        let captures = format.captures(line)?;
+        let start = captures.get(0)?.start();
+        let is_leading = *anywhere || !line[..start].contains(char::is_whitespace);
+        (!leading || is_leading).then_some(captures)
+    })
+}
+
+/// Converts the leftmost of `matches` that is a valid time.
+fn leftmost<'a>(matches: impl Iterator<Item = Captures<'a>>, default_year: i32) -> Option<i64> {
+    matches
+        .filter_map(|captures| {
+            let start = captures.get(0)?.start();
+            Some((start, to_millis(&captures, default_year)?))
+        })
+        .min_by_key(|(start, _)| *start)
+        .map(|(_, millis)| millis)
+}
+
+/// Finds the leftmost timestamp in `line` and returns it as milliseconds
+/// since the Unix epoch. Timestamps without a zone are taken as UTC, and
+/// syslog timestamps, which carry no year, are placed in `default_year`.
+pub fn find_timestamp(line: &str, default_year: i32) -> Option<i64> {
+    leftmost(matches(line, false), default_year)
+}
+
+/// Like [`find_timestamp`], but only takes a timestamp the line starts
+/// with, not one mentioned further on as in `Caused by: … at 2021-…`.
+pub fn leading_timestamp(line: &str, default_year: i32) -> Option<i64> {
+    leftmost(matches(line, true), default_year)
+}
+
+/// Returns true when `line` starts with a timestamp, see
+/// [`leading_timestamp`].
+pub fn starts_with_timestamp(line: &str) -> bool {
+    matches(line, true).next().is_some()
+}
+
+/// Parses a point in time given on the command line, in milliseconds since
+/// the epoch: either a timestamp in one of the recognized formats, a date
+/// like `2022-07-15`, or a duration before `now` like `90s`, `15m`, `2h`,
+/// `3d` or `1w`.
+pub fn parse_time(value: &str, now: i64) -> Result<i64> {
+    static RELATIVE: OnceLock<Regex> = OnceLock::new();
+    let relative = RELATIVE
+        .get_or_init(|| Regex::new(r"^(\d+)([smhdw])$").expect("relative time is a valid regex"));
+
+    if let Some(captures) = relative.captures(value) {
+        let amount: i64 = captures[1].parse()?;
+        let duration = match &captures[2] {
+            "s" => Duration::try_seconds(amount),
+            "m" => Duration::try_minutes(amount),
+            "h" => Duration::try_hours(amount),
+            "d" => Duration::try_days(amount),
+            _ => Duration::try_weeks(amount),
+        };
+        if let Some(duration) = duration {
+            return Ok(now - duration.num_milliseconds());
+        }
+    } else if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let midnight = date.and_hms_opt(0, 0, 0).expect("midnight exists");
+        return Ok(Utc.from_utc_datetime(&midnight).timestamp_millis());
+    } else {
+        let year = Utc
+            .timestamp_millis_opt(now)
+            .single()
+            .map_or(1970, |now| now.year());
+        if let Some(timestamp) = find_timestamp(value, year) {
+            return Ok(timestamp);
+        }
+    }
+
+    bail!(
+        "Invalid time ({}), expected a timestamp like 2022-07-15T10:00 or a duration like 2h",
+        value
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_timestamp, leading_timestamp, parse_time, starts_with_timestamp};
+    use anyhow::Result;
+
+    // 2022-07-15T10:00:01Z
+    const EXPECTED: i64 = 1_657_879_201_000;
+
+    #[test]
+    fn find_timestamp_test() {
+        let lines = [
+            "2022-07-15T10:00:01Z INFO started",
+            "2022-07-15 10:00:01 INFO started",
+            "2022-07-15T12:00:01+02:00 INFO started",
+            "2022-07-15T10:00:01.000Z INFO started",
+            r#"127.0.0.1 - - [15/Jul/2022:10:00:01 +0000] "GET / HTTP/1.1" 200 2"#,
+            "Jul 15 10:00:01 host sshd[42]: Accepted publickey",
+        ];
+
+        for line in lines {
+            assert_eq!(find_timestamp(line, 2022), Some(EXPECTED), "{}", line);
+        }
+
+        assert_eq!(
+            find_timestamp("2022-07-15T10:00:01,250 INFO", 2022),
+            Some(EXPECTED + 250)
+        );
+        assert_eq!(find_timestamp("\tat com.example.Main.run", 2022), None);
+
+        for line in ["1657879201 started", "[1657879201000] started"] {
+            assert_eq!(find_timestamp(line, 2022), Some(EXPECTED), "{}", line);
+        }
+        assert_eq!(
+            find_timestamp("1657879201.25 started", 2022),
+            Some(EXPECTED + 250)
+        );
+        assert_eq!(find_timestamp("pid 1657879201 started", 2022), None);
+
+        // The leftmost timestamp wins, whatever its format.
+        let request = r#"127.0.0.1 - - [15/Jul/2022:10:00:01 +0000] "GET /?from=2021-01-01T00:00 HTTP/1.1" 200 2"#;
+        assert_eq!(find_timestamp(request, 2022), Some(EXPECTED));
+        assert_eq!(leading_timestamp(request, 2022), Some(EXPECTED));
+
+        let cause = "Caused by: timeout at 2021-01-01T00:00:00Z";
+        assert!(find_timestamp(cause, 2022).is_some());
+        assert_eq!(leading_timestamp(cause, 2022), None);
+    }
+
+    #[test]
+    fn starts_with_timestamp_test() {
+        assert!(starts_with_timestamp("2022-07-15T10:00:01Z ERROR boom"));
+        assert!(starts_with_timestamp("<13>Jul 15 10:00:01 host app: boom"));
+        assert!(starts_with_timestamp("[1657879201000] boom"));
+        assert!(!starts_with_timestamp("\tat com.example.Main.run"));
+        assert!(!starts_with_timestamp(
+            "Caused by: timeout at 2022-07-15T10:00:01Z"
+        ));
+    }
+
+    #[test]
+    fn parse_time_test() -> Result<()> {
+        assert_eq!(parse_time("2022-07-15T10:00:01Z", 0)?, EXPECTED);
+        assert_eq!(parse_time("2022-07-15T10:00", 0)?, EXPECTED - 1000);
+        assert_eq!(parse_time("2022-07-15", 0)?, EXPECTED - 36_001_000);
+        assert_eq!(parse_time("1657879201", 0)?, EXPECTED);
+        assert_eq!(parse_time("2h", EXPECTED)?, EXPECTED - 7_200_000);
+        assert_eq!(parse_time("1w", EXPECTED)?, EXPECTED - 604_800_000);
+        assert!(parse_time("yesterday", EXPECTED).is_err());
+        Ok(())
+    }
+}
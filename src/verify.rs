@@ -0,0 +1,165 @@
+use anyhow::Result;
+use std::io::{self, copy, sink, stdout, BufRead, Read, Write};
+
+use crate::decompress::{decompress_into, Encoding};
+use crate::input::with_input;
+use crate::report::{write_table, Align};
+
+/// Reader that counts the bytes taken from it.
+struct CountingReader<R> {
+    inner: R,
+    count: u64,
+}
+
+impl<R: BufRead> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: BufRead> BufRead for CountingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.count += amt as u64;
+        self.inner.consume(amt);
+    }
+}
+
+/// Writer that drops everything but its length.
+#[derive(Default)]
+struct CountingSink {
+    count: u64,
+}
+
+impl Write for CountingSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.count += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Outcome of decoding one input file.
+#[derive(Debug)]
+pub struct VerifyReport {
+    pub path: String,
+    /// Bytes read from the file, up to the failure if any.
+    pub compressed: u64,
+    /// Bytes decoded from the file, up to the failure if any.
+    pub uncompressed: u64,
+    pub result: Result<Encoding, String>,
+}
+
+impl VerifyReport {
+    /// Uncompressed size per compressed byte.
+    fn ratio(&self) -> Option<f64> {
+        (self.compressed > 0).then(|| self.uncompressed as f64 / self.compressed as f64)
+    }
+}
+
+/// Decodes the input at `path` to the end, which checks the CRC32 and ISIZE
+/// trailers of gzip members and the checksums of the other formats.
+pub fn verify_file(path: &str) -> VerifyReport {
+    let mut compressed = 0;
+    let mut decoded = CountingSink::default();
+
+    let result = with_input(path, |reader| -> Result<Encoding> {
+        let mut reader = CountingReader {
+            inner: reader,
+            count: 0,
+        };
+        let result = decompress_into(&mut reader, path, &mut decoded).and_then(|encoding| {
+            // Anything a decoder left behind belongs to the file as well.
+            copy(&mut reader, &mut sink())?;
+            Ok(encoding)
+        });
+        compressed = reader.count;
+        result
+    });
+
+    VerifyReport {
+        path: path.to_owned(),
+        compressed,
+        uncompressed: decoded.count,
+        result: result.map_err(|error| format!("{:#}", error)),
+    }
+}
+
+/// Prints one row per verified file to standard output.
+pub fn print_verify_summary(reports: &[VerifyReport]) -> io::Result<()> {
+    let rows: Vec<Vec<String>> = reports
+        .iter()
+        .map(|report| {
+            let (status, details) = match &report.result {
+                Ok(encoding) => ("ok", encoding.to_string()),
+                Err(error) => ("failed", error.clone()),
+            };
+            vec![
+                report.path.clone(),
+                status.to_owned(),
+                report.compressed.to_string(),
+                report.uncompressed.to_string(),
+                report
+                    .ratio()
+                    .map_or_else(|| String::from("-"), |ratio| format!("{:.2}", ratio)),
+                details,
+            ]
+        })
+        .collect();
+
+    write_table(
+        &mut stdout().lock(),
+        &[
+            ("FILE", Align::Left),
+            ("STATUS", Align::Left),
+            ("COMPRESSED", Align::Right),
+            ("UNCOMPRESSED", Align::Right),
+            ("RATIO", Align::Right),
+            ("DETAILS", Align::Left),
+        ],
+        &rows,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::fs::write;
+    use std::io::Write;
+
+    use super::verify_file;
+    use anyhow::Result;
+
+    #[test]
+    fn verify_file_test() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"Hello World\n")?;
+        let mut member = encoder.finish()?;
+
+        let good = dir.path().join("good.log.1.gz");
+        write(&good, &member)?;
+        let report = verify_file(good.to_str().unwrap());
+        assert!(report.result.is_ok());
+        assert_eq!(report.compressed, member.len() as u64);
+        assert_eq!(report.uncompressed, 12);
+
+        // ISIZE one off, the data and CRC32 being intact.
+        let isize_at = member.len() - 4;
+        member[isize_at] += 1;
+        let bad = dir.path().join("bad.log.2.gz");
+        write(&bad, &member)?;
+        assert!(verify_file(bad.to_str().unwrap()).result.is_err());
+
+        Ok(())
+    }
+}